@@ -16,6 +16,7 @@ use opentracingrust::FinishedSpan;
 use super::super::thrift_gen::zipkin_core::Endpoint;
 use super::super::thrift_gen::zipkin_core::Span;
 use super::super::Result;
+use super::is_reportable;
 use super::thrift_encode;
 
 /// Submit finished spans to Zipkin over HTTP.
@@ -44,7 +45,12 @@ impl HttpCollector {
     }
 
     /// Append a span to the send buffer.
+    ///
+    /// Spans that are not sampled are dropped, unless the debug flag is set.
     pub fn collect(&mut self, span: FinishedSpan) {
+        if !is_reportable(&span) {
+            return;
+        }
         let span = thrift_encode(&span, &self.endpoint);
         self.spans.push(span);
     }
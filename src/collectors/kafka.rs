@@ -12,6 +12,7 @@ use thrift::protocol::TOutputProtocol;
 use thrift::transport::TBufferedWriteTransport;
 
 use super::super::ZipkinEndpoint;
+use super::is_reportable;
 use super::thrift_encode;
 
 
@@ -38,8 +39,13 @@ impl KafkaCollector {
     }
 
     /// Sends a finished span to Zipkin.
+    ///
+    /// Spans that are not sampled are dropped, unless the debug flag is set.
     // TODO: Change returned error type to wrap kafa and encoding errors.
     pub fn collect(&mut self, span: FinishedSpan) -> Result<()> {
+        if !is_reportable(&span) {
+            return Ok(());
+        }
         let encoded = thrift_encode(&span, &self.endpoint);
         let mut buffer: Vec<u8> = Vec::new();
 
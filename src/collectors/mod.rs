@@ -14,6 +14,7 @@ use super::tracer::ZipkinContext;
 pub mod http;
 #[cfg(feature = "kafka_transport")]
 pub mod kafka;
+pub mod tail;
 
 const MICROSECOND: u64 = 1_000_000;
 
@@ -55,6 +56,17 @@ fn encode_tag_value(value: &TagValue) -> (Vec<u8>, zipkin_core::AnnotationType)
     (buffer, zipkin_core::AnnotationType::String)
 }
 
+/// Checks if a finished span should be reported to Zipkin.
+///
+/// Following the B3 specification, debug spans are always reported
+/// while other spans are reported only if they are sampled.
+fn is_reportable(span: &FinishedSpan) -> bool {
+    let context = span.context().impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    context.debug() || context.sampled()
+}
+
 /// Encodes a finished span into a thrift message for Zipkin.
 pub fn thrift_encode(span: &FinishedSpan, endpoint: &zipkin_core::Endpoint) -> zipkin_core::Span {
     // Extract span details.
@@ -83,6 +95,19 @@ pub fn thrift_encode(span: &FinishedSpan, endpoint: &zipkin_core::Endpoint) -> z
         binary_annotations.push(annotation);
     }
 
+    // Record the sampling decision made for this span, if any.
+    if let Some((kind, param)) = context.sampler() {
+        for (key, value) in [("sampler.type", kind), ("sampler.param", param)] {
+            let annotation = zipkin_core::BinaryAnnotation::new(
+                Some(String::from(key)),                   // key
+                Some(value.into_bytes()),                  // value
+                Some(zipkin_core::AnnotationType::String), // annotation_type
+                Some(endpoint.clone()),                    // host
+            );
+            binary_annotations.push(annotation);
+        }
+    }
+
     // Convert logs into annotations.
     let mut annotations = Vec::new();
     for log in span.logs() {
@@ -100,6 +125,24 @@ pub fn thrift_encode(span: &FinishedSpan, endpoint: &zipkin_core::Endpoint) -> z
         annotations.push(annotation);
     }
 
+    // Shared spans follow the classic Zipkin convention: the server records `sr` and `ss`
+    // while the client owns the span timestamp and duration.
+    let (timestamp, duration) = match context.shared() {
+        false => (Some(timestamp), Some(duration)),
+        true => {
+            let finish = compute_duration(UNIX_EPOCH, *span.finish_time());
+            for (value, time) in [("sr", timestamp), ("ss", finish)] {
+                let annotation = zipkin_core::Annotation::new(
+                    Some(time),                // timestamp
+                    Some(String::from(value)), // value
+                    Some(endpoint.clone()),    // host
+                );
+                annotations.push(annotation);
+            }
+            (None, None)
+        }
+    };
+
     // Ensure at least an annotation is present to carry the endpoint information.
     if annotations.is_empty() && binary_annotations.is_empty() {
         let annotation = zipkin_core::BinaryAnnotation::new(
@@ -120,8 +163,8 @@ pub fn thrift_encode(span: &FinishedSpan, endpoint: &zipkin_core::Endpoint) -> z
         Some(annotations),                             // annotations
         Some(binary_annotations),                      // binary_annotations
         Some(context.debug()),                         // debug
-        Some(timestamp),                               // timestamp
-        Some(duration),                                // duration
+        timestamp,                                     // timestamp
+        duration,                                      // duration
         Some(high as i64),                             // trace_id_high
     )
 }
@@ -132,11 +175,19 @@ mod tests {
     use std::time::UNIX_EPOCH;
 
     use opentracingrust::FinishedSpan;
+    use opentracingrust::ImplContextBox;
+    use opentracingrust::SpanContext;
+    use opentracingrust::StartOptions;
 
+    use super::super::ProbabilisticSampler;
     use super::super::ZipkinTracer;
+    use super::super::ZipkinTracerOptions;
     use super::super::tracer::ZipkinContext;
+    use super::super::tracer::SamplingState;
+    use super::super::tracer::ZipkinContextOptions;
     use super::super::thrift_gen::zipkin_core;
     use super::MICROSECOND;
+    use super::is_reportable;
     use super::thrift_encode;
 
     fn context(span: &FinishedSpan) -> ZipkinContext {
@@ -151,6 +202,16 @@ mod tests {
         receiver.recv().unwrap()
     }
 
+    fn mock_child_span(options: ZipkinContextOptions) -> FinishedSpan {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let parent = ZipkinContext::new_with_options(options);
+        let parent = SpanContext::new(ImplContextBox::new(parent));
+        let options = StartOptions::default().child_of(parent);
+        let span = tracer.span_with_options("test", options);
+        span.finish().unwrap();
+        receiver.recv().unwrap()
+    }
+
     fn mocks() -> (FinishedSpan, ZipkinContext, zipkin_core::Span) {
         let span = mock_span();
         let context = context(&span);
@@ -185,4 +246,82 @@ mod tests {
         let timestamp = i64::try_from(timestamp).unwrap();
         assert_eq!(encoded.timestamp.unwrap(), timestamp);
     }
+
+    #[test]
+    fn report_sampled_spans() {
+        let span = mock_child_span(ZipkinContextOptions::default().sampled(true));
+        assert!(is_reportable(&span));
+    }
+
+    #[test]
+    fn skip_unsampled_spans() {
+        let span = mock_child_span(ZipkinContextOptions::default().sampled(false));
+        assert!(!is_reportable(&span));
+    }
+
+    #[test]
+    fn report_debug_spans() {
+        let options = ZipkinContextOptions::default().debug(true).sampled(false);
+        let span = mock_child_span(options);
+        assert!(is_reportable(&span));
+    }
+
+    fn annotation(encoded: &zipkin_core::Span, key: &str) -> Option<String> {
+        encoded.binary_annotations.as_ref().unwrap().iter()
+            .find(|annotation| annotation.key.as_ref().map(|k| &k[..]) == Some(key))
+            .map(|annotation| String::from_utf8(annotation.value.clone().unwrap()).unwrap())
+    }
+
+    #[test]
+    fn serialise_sampler_decision() {
+        let options = ZipkinTracerOptions::default().sampler(ProbabilisticSampler::new(1.0));
+        let (tracer, receiver) = ZipkinTracer::new_with_options(options);
+        tracer.span("test").finish().unwrap();
+        let span = receiver.recv().unwrap();
+        let endpoint = zipkin_core::Endpoint::new(None, None, None, None);
+        let encoded = thrift_encode(&span, &endpoint);
+        assert_eq!(annotation(&encoded, "sampler.type"), Some(String::from("probabilistic")));
+        assert_eq!(annotation(&encoded, "sampler.param"), Some(String::from("1")));
+    }
+
+    #[test]
+    fn serialise_no_sampler_decision_for_children() {
+        let span = mock_child_span(ZipkinContextOptions::default());
+        let endpoint = zipkin_core::Endpoint::new(None, None, None, None);
+        let encoded = thrift_encode(&span, &endpoint);
+        assert_eq!(annotation(&encoded, "sampler.type"), None);
+        assert_eq!(annotation(&encoded, "sampler.param"), None);
+    }
+
+    #[test]
+    fn serialise_debug_override() {
+        let options = ZipkinContextOptions::default()
+            .debug(true)
+            .sampling_state(SamplingState::Defer);
+        let span = mock_child_span(options);
+        let endpoint = zipkin_core::Endpoint::new(None, None, None, None);
+        let encoded = thrift_encode(&span, &endpoint);
+        assert_eq!(annotation(&encoded, "sampler.type"), Some(String::from("debug")));
+    }
+
+    #[test]
+    fn serialise_shared_spans() {
+        let options = ZipkinContextOptions::default().parent_span_id(1).span_id(2);
+        let parent = ZipkinContext::new_with_options(options);
+        let parent = SpanContext::new(ImplContextBox::new(parent));
+        let (tracer, receiver) = ZipkinTracer::new();
+        let options = StartOptions::default().child_of(ZipkinContext::join(&parent));
+        tracer.span_with_options("test", options).finish().unwrap();
+        let span = receiver.recv().unwrap();
+        let endpoint = zipkin_core::Endpoint::new(None, None, None, None);
+        let encoded = thrift_encode(&span, &endpoint);
+        assert_eq!(encoded.id.unwrap(), 2);
+        assert_eq!(encoded.parent_id, Some(1));
+        assert_eq!(encoded.timestamp, None);
+        assert_eq!(encoded.duration, None);
+        let values: Vec<String> = encoded.annotations.unwrap().into_iter()
+            .map(|annotation| annotation.value.unwrap())
+            .collect();
+        assert_eq!(values, vec![String::from("sr"), String::from("ss")]);
+    }
 }
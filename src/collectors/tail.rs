@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use std::time::Instant;
+
+use opentracingrust::FinishedSpan;
+use opentracingrust::TagValue;
+
+use super::super::sampler::ProbabilisticSampler;
+use super::super::tracer::ZipkinContext;
+
+
+/// Finished spans of a trace waiting for the sampling decision.
+struct BufferedTrace {
+    first_seen: Instant,
+    spans: Vec<FinishedSpan>,
+}
+
+
+/// Buffers finished spans by trace to decide which traces to forward to a collector.
+///
+/// Spans are grouped by trace ID and held for a configurable window from the moment
+/// the first span of the trace is received.
+/// Once the window is over, the trace is forwarded if any of these is true:
+///
+///   * A span has an `error` tag set to `true`.
+///   * The trace (from the earliest span start to the latest span finish)
+///     took at least the configured latency threshold.
+///   * The trace is sampled by the baseline probability (keyed on the trace ID).
+///
+/// Other traces are discarded.
+/// Spans of a trace received after the trace was decided start a new window.
+///
+/// Collectors drop spans that are not sampled so tail sampling is
+/// most useful when all root spans are sampled by the tracer.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut buffer = TailSamplingBuffer::new(
+///     TailSamplingOpts::new(Duration::from_secs(30))
+///         .latency_threshold(Duration::from_millis(500))
+///         .baseline_rate(0.01)
+/// );
+/// let mut reporter = ReporterThread::new(receiver, move |span| {
+///     buffer.collect(span);
+///     for span in buffer.flush_expired() {
+///         collector.collect(span);
+///     }
+/// });
+/// ```
+pub struct TailSamplingBuffer {
+    baseline: ProbabilisticSampler,
+    latency_threshold: Option<Duration>,
+    traces: HashMap<(u64, u64), BufferedTrace>,
+    window: Duration,
+}
+
+impl TailSamplingBuffer {
+    /// Creates a new, empty, buffer with the given options.
+    pub fn new(opts: TailSamplingOpts) -> TailSamplingBuffer {
+        TailSamplingBuffer {
+            baseline: ProbabilisticSampler::new(opts.baseline_rate),
+            latency_threshold: opts.latency_threshold,
+            traces: HashMap::new(),
+            window: opts.window,
+        }
+    }
+
+    /// Append a span to the buffer of its trace.
+    pub fn collect(&mut self, span: FinishedSpan) {
+        self.collect_at(span, Instant::now());
+    }
+
+    /// Decide on all buffered traces, regardless of their window.
+    ///
+    /// Returns the spans of traces that should be forwarded to a collector.
+    /// Call this method before the buffer is dropped to avoid losing buffered spans.
+    pub fn flush(&mut self) -> Vec<FinishedSpan> {
+        let mut spans = Vec::new();
+        let traces: Vec<BufferedTrace> = self.traces.drain().map(|(_, trace)| trace).collect();
+        for trace in traces {
+            if self.keep(&trace) {
+                spans.extend(trace.spans);
+            }
+        }
+        spans
+    }
+
+    /// Decide on buffered traces with an expired window.
+    ///
+    /// Returns the spans of traces that should be forwarded to a collector.
+    ///
+    /// You should call this method after collecting every span and periodically
+    /// even if you do not receive spans to ensure traces are not held for too long.
+    pub fn flush_expired(&mut self) -> Vec<FinishedSpan> {
+        self.flush_expired_at(Instant::now())
+    }
+
+    /// Number of traces currently buffered.
+    pub fn len(&self) -> usize {
+        self.traces.len()
+    }
+
+    /// Checks if no trace is buffered.
+    pub fn is_empty(&self) -> bool {
+        self.traces.is_empty()
+    }
+
+    /// Append a span to the buffer of its trace as if it was received at the given time.
+    fn collect_at(&mut self, span: FinishedSpan, now: Instant) {
+        let key = {
+            let context = span.context().impl_context::<ZipkinContext>().expect(
+                "Invalid SpanContext, was it created by ZipkinTracer?"
+            );
+            context.trace_id().split()
+        };
+        let trace = self.traces.entry(key).or_insert_with(|| BufferedTrace {
+            first_seen: now,
+            spans: Vec::new(),
+        });
+        trace.spans.push(span);
+    }
+
+    /// Decide on traces with a window expired at the given time.
+    fn flush_expired_at(&mut self, now: Instant) -> Vec<FinishedSpan> {
+        let window = self.window;
+        let expired: Vec<(u64, u64)> = self.traces.iter()
+            .filter(|(_, trace)| now.duration_since(trace.first_seen) >= window)
+            .map(|(key, _)| *key)
+            .collect();
+        let mut spans = Vec::new();
+        for key in expired {
+            let trace = self.traces.remove(&key).unwrap();
+            if self.keep(&trace) {
+                spans.extend(trace.spans);
+            }
+        }
+        spans
+    }
+
+    /// Decide if a trace should be forwarded.
+    fn keep(&self, trace: &BufferedTrace) -> bool {
+        let errored = trace.spans.iter().any(|span| match span.tags().get("error") {
+            Some(TagValue::Boolean(error)) => *error,
+            Some(TagValue::String(error)) => error == "true",
+            _ => false,
+        });
+        if errored {
+            return true;
+        }
+
+        if let Some(threshold) = self.latency_threshold {
+            let start = trace.spans.iter().map(|span| *span.start_time()).min();
+            let finish = trace.spans.iter().map(|span| *span.finish_time()).max();
+            if let (Some(start), Some(finish)) = (start, finish) {
+                if finish.duration_since(start).map(|d| d >= threshold).unwrap_or(false) {
+                    return true;
+                }
+            }
+        }
+
+        let context = trace.spans[0].context().impl_context::<ZipkinContext>().unwrap();
+        let (_, low) = context.trace_id().split();
+        self.baseline.sample_id(low)
+    }
+}
+
+
+/// Tail sampling buffer options.
+pub struct TailSamplingOpts {
+    baseline_rate: f64,
+    latency_threshold: Option<Duration>,
+    window: Duration,
+}
+
+impl TailSamplingOpts {
+    /// Default tail sampling options.
+    ///
+    /// The window to buffer traces for is required.
+    /// By default only traces with errors are forwarded.
+    pub fn new(window: Duration) -> TailSamplingOpts {
+        TailSamplingOpts {
+            baseline_rate: 0.0,
+            latency_threshold: None,
+            window,
+        }
+    }
+
+    /// Set the fraction of traces to forward regardless of errors or latency.
+    pub fn baseline_rate(mut self, rate: f64) -> TailSamplingOpts {
+        self.baseline_rate = rate;
+        self
+    }
+
+    /// Set the duration above which traces are always forwarded.
+    pub fn latency_threshold(mut self, threshold: Duration) -> TailSamplingOpts {
+        self.latency_threshold = Some(threshold);
+        self
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+    use std::time::SystemTime;
+
+    use opentracingrust::FinishedSpan;
+    use opentracingrust::SpanContext;
+    use opentracingrust::SpanReceiver;
+    use opentracingrust::StartOptions;
+    use opentracingrust::Tracer;
+
+    use super::super::super::ZipkinTracer;
+    use super::TailSamplingBuffer;
+    use super::TailSamplingOpts;
+
+    fn mock_span(
+        tracer: &Tracer, receiver: &SpanReceiver, parent: Option<SpanContext>,
+        duration: Duration, error: bool
+    ) -> FinishedSpan {
+        let start = SystemTime::now();
+        let options = StartOptions::default().start_time(start);
+        let options = match parent {
+            None => options,
+            Some(parent) => options.child_of(parent),
+        };
+        let mut span = tracer.span_with_options("test", options);
+        if error {
+            span.tag("error", true);
+        }
+        span.finish_time(start + duration);
+        span.finish().unwrap();
+        receiver.recv().unwrap()
+    }
+
+    fn buffer() -> TailSamplingBuffer {
+        TailSamplingBuffer::new(
+            TailSamplingOpts::new(Duration::from_secs(10))
+                .latency_threshold(Duration::from_secs(1))
+        )
+    }
+
+    #[test]
+    fn spans_are_grouped_by_trace() {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let mut buffer = buffer();
+        let root = mock_span(&tracer, &receiver, None, Duration::from_millis(1), false);
+        let parent = root.context().clone();
+        buffer.collect(root);
+        let child = mock_span(&tracer, &receiver, Some(parent), Duration::from_millis(1), true);
+        buffer.collect(child);
+        let other = mock_span(&tracer, &receiver, None, Duration::from_millis(1), false);
+        buffer.collect(other);
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.flush().len(), 2);
+    }
+
+    #[test]
+    fn traces_wait_for_the_window() {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let mut buffer = buffer();
+        let now = Instant::now();
+        let span = mock_span(&tracer, &receiver, None, Duration::from_millis(1), true);
+        buffer.collect_at(span, now);
+        assert_eq!(buffer.flush_expired_at(now + Duration::from_secs(5)).len(), 0);
+        assert_eq!(buffer.flush_expired_at(now + Duration::from_secs(10)).len(), 1);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn keep_errors() {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let mut buffer = buffer();
+        let span = mock_span(&tracer, &receiver, None, Duration::from_millis(1), true);
+        buffer.collect(span);
+        assert_eq!(buffer.flush().len(), 1);
+    }
+
+    #[test]
+    fn keep_slow_traces() {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let mut buffer = buffer();
+        let span = mock_span(&tracer, &receiver, None, Duration::from_secs(2), false);
+        buffer.collect(span);
+        assert_eq!(buffer.flush().len(), 1);
+    }
+
+    #[test]
+    fn discard_boring_traces() {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let mut buffer = buffer();
+        let span = mock_span(&tracer, &receiver, None, Duration::from_millis(1), false);
+        buffer.collect(span);
+        assert_eq!(buffer.flush().len(), 0);
+    }
+
+    #[test]
+    fn keep_baseline_traces() {
+        let (tracer, receiver) = ZipkinTracer::new();
+        let mut buffer = TailSamplingBuffer::new(
+            TailSamplingOpts::new(Duration::from_secs(10)).baseline_rate(1.0)
+        );
+        let span = mock_span(&tracer, &receiver, None, Duration::from_millis(1), false);
+        buffer.collect(span);
+        assert_eq!(buffer.flush().len(), 1);
+    }
+}
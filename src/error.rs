@@ -4,7 +4,10 @@ use std::fmt;
 /// Enumeration of all errors returned by the crate.
 #[derive(Debug)]
 pub enum Error {
+    Io(::std::io::Error),
+    Json(::serde_json::Error),
     Reqwest(::reqwest::Error),
+    SamplerConfig(String),
     Thrift(::thrift::Error),
 }
 
@@ -13,12 +16,27 @@ impl StdError for Error {}
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Error::Io(ref io) => fmt::Display::fmt(io, f),
+            Error::Json(ref json) => fmt::Display::fmt(json, f),
             Error::Reqwest(ref reqwest) => fmt::Display::fmt(reqwest, f),
+            Error::SamplerConfig(ref msg) => write!(f, "invalid sampler configuration: {}", msg),
             Error::Thrift(ref thrift) => fmt::Display::fmt(thrift, f),
         }
     }
 }
 
+impl From<::std::io::Error> for Error {
+    fn from(error: ::std::io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<::serde_json::Error> for Error {
+    fn from(error: ::serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
 impl From<::reqwest::Error> for Error {
     fn from(error: ::reqwest::Error) -> Error {
         Error::Reqwest(error)
@@ -68,6 +68,7 @@ extern crate thrift;
 
 mod collectors;
 mod error;
+mod sampler;
 mod thrift_gen;
 mod tracer;
 
@@ -75,8 +76,43 @@ pub use self::collectors::http::HttpCollector;
 pub use self::collectors::http::HttpCollectorOpts;
 #[cfg(feature = "kafka_transport")]
 pub use self::collectors::kafka::KafkaCollector;
+pub use self::collectors::tail::TailSamplingBuffer;
+pub use self::collectors::tail::TailSamplingOpts;
 
 pub use self::error::Error;
 pub use self::error::Result;
+pub use self::sampler::ConstSampler;
+pub use self::sampler::FileSampler;
+pub use self::sampler::ProbabilisticSampler;
+pub use self::sampler::RateLimitingSampler;
+pub use self::sampler::RuleSampler;
+pub use self::sampler::Sampler;
+pub use self::sampler::SamplingDecision;
+pub use self::sampler::SamplingRequest;
 pub use self::thrift_gen::zipkin_core::Endpoint as ZipkinEndpoint;
+pub use self::tracer::B3MultiPropagator;
+pub use self::tracer::B3SinglePropagator;
+pub use self::tracer::BinaryPropagator;
+pub use self::tracer::CompositePropagator;
+pub use self::tracer::GrpcTraceBinPropagator;
+pub use self::tracer::IdGenerator;
+pub use self::tracer::InvalidContext;
+pub use self::tracer::InvalidContextCounter;
+pub use self::tracer::InvalidContextPolicy;
+pub use self::tracer::JaegerPropagator;
+pub use self::tracer::Propagator;
+pub use self::tracer::RandomIdGenerator;
+pub use self::tracer::SamplingState;
+pub use self::tracer::SeededIdGenerator;
+pub use self::tracer::TextMapPropagator;
+pub use self::tracer::ThriftBinaryPropagator;
+pub use self::tracer::ThriftProtocol;
+pub use self::tracer::TimeIdGenerator;
+pub use self::tracer::TraceContextPropagator;
+pub use self::tracer::TraceID;
+pub use self::tracer::TraceIdMode;
+pub use self::tracer::XRayPropagator;
+pub use self::tracer::ZipkinContext;
+pub use self::tracer::ZipkinContextOptions;
 pub use self::tracer::ZipkinTracer;
+pub use self::tracer::ZipkinTracerOptions;
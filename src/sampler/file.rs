@@ -0,0 +1,308 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+
+use serde_json;
+use serde_json::Value;
+
+use super::super::Error;
+use super::super::Result;
+use super::ConstSampler;
+use super::ProbabilisticSampler;
+use super::RateLimitingSampler;
+use super::RuleSampler;
+use super::Sampler;
+use super::SamplingDecision;
+use super::SamplingRequest;
+
+
+/// Builds a sampler from a `{"type": ..., "param": ...}` JSON object.
+fn parse_strategy(strategy: &Value) -> Result<Box<dyn Sampler>> {
+    let kind = strategy.get("type").and_then(Value::as_str).ok_or_else(|| {
+        Error::SamplerConfig(String::from("sampling strategies must have a string type"))
+    })?;
+    let param = strategy.get("param");
+    match kind {
+        "const" => {
+            let sampled = match param {
+                Some(Value::Bool(sampled)) => *sampled,
+                Some(param) if param.is_number() => param.as_f64() != Some(0.0),
+                _ => return Err(Error::SamplerConfig(
+                    String::from("const strategies need a boolean param")
+                )),
+            };
+            match sampled {
+                true => Ok(Box::new(ConstSampler::always())),
+                false => Ok(Box::new(ConstSampler::never())),
+            }
+        }
+        "probabilistic" => {
+            let rate = param.and_then(Value::as_f64).ok_or_else(|| {
+                Error::SamplerConfig(String::from("probabilistic strategies need a numeric param"))
+            })?;
+            Ok(Box::new(ProbabilisticSampler::new(rate)))
+        }
+        "ratelimiting" => {
+            let rate = param.and_then(Value::as_f64).ok_or_else(|| {
+                Error::SamplerConfig(String::from("ratelimiting strategies need a numeric param"))
+            })?;
+            Ok(Box::new(RateLimitingSampler::new(rate)))
+        }
+        kind => Err(Error::SamplerConfig(format!("unsupported strategy type '{}'", kind))),
+    }
+}
+
+/// Builds a sampler from a JSON configuration.
+///
+/// See `FileSampler` for the configuration format.
+fn parse_config(config: &Value) -> Result<Box<dyn Sampler>> {
+    let default = config.get("default").ok_or_else(|| {
+        Error::SamplerConfig(String::from("a default strategy is required"))
+    })?;
+    let default = parse_strategy(default)?;
+    let rules = match config.get("rules") {
+        None => return Ok(default),
+        Some(Value::Array(rules)) => rules,
+        Some(_) => return Err(Error::SamplerConfig(String::from("rules must be a list"))),
+    };
+    let mut sampler = RuleSampler::new(default);
+    for rule in rules {
+        let operation = rule.get("operation").and_then(Value::as_str).ok_or_else(|| {
+            Error::SamplerConfig(String::from("rules must have a string operation"))
+        })?;
+        sampler = sampler.rule(operation, parse_strategy(rule)?);
+    }
+    Ok(Box::new(sampler))
+}
+
+/// Reads and parses a sampler configuration file.
+fn load(path: &Path) -> Result<Box<dyn Sampler>> {
+    let config = fs::read(path)?;
+    let config: Value = serde_json::from_slice(&config)?;
+    parse_config(&config)
+}
+
+
+/// Tracks when the configuration file was last checked for changes.
+struct WatchState {
+    last_check: Instant,
+    modified: Option<SystemTime>,
+}
+
+
+/// Samples traces according to a strategy loaded from a JSON file.
+///
+/// The file is checked for changes at most once every `check_interval`
+/// (10 seconds by default) when root spans are sampled.
+/// If the file was modified it is loaded again.
+/// Errors loading changed files are ignored and the current strategy is kept:
+/// use `FileSampler::reload` to reload the file explicitly and check for errors.
+///
+/// Share the sampler with an `Arc` to call `reload` after the tracer is created.
+///
+/// # Configuration format
+///
+/// ```json
+/// {
+///   "default": {"type": "probabilistic", "param": 0.001},
+///   "rules": [
+///     {"operation": "checkout", "type": "const", "param": true},
+///     {"operation": "health*", "type": "ratelimiting", "param": 1}
+///   ]
+/// }
+/// ```
+///
+/// Supported strategy types are:
+///
+///   * `const`: sample all (`true`) or no (`false`) traces.
+///   * `probabilistic`: see `ProbabilisticSampler`.
+///   * `ratelimiting`: see `RateLimitingSampler`.
+///
+/// Rules are optional and are matched as described by `RuleSampler`.
+pub struct FileSampler {
+    check_interval: Duration,
+    path: PathBuf,
+    sampler: RwLock<Box<dyn Sampler>>,
+    watch: Mutex<WatchState>,
+}
+
+impl FileSampler {
+    /// Returns a sampler configured by the given file.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<FileSampler> {
+        let path = path.into();
+        let modified = fs::metadata(&path)?.modified().ok();
+        let sampler = load(&path)?;
+        let watch = WatchState {
+            last_check: Instant::now(),
+            modified,
+        };
+        Ok(FileSampler {
+            check_interval: Duration::from_secs(10),
+            path,
+            sampler: RwLock::new(sampler),
+            watch: Mutex::new(watch),
+        })
+    }
+
+    /// Set the minimum delay between checks for configuration changes.
+    pub fn check_interval(mut self, interval: Duration) -> FileSampler {
+        self.check_interval = interval;
+        self
+    }
+}
+
+impl FileSampler {
+    /// Access the path to the configuration file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Loads the configuration file again.
+    ///
+    /// If the file can't be loaded the current strategy is kept.
+    pub fn reload(&self) -> Result<()> {
+        let mut watch = self.watch.lock().expect("FileSampler lock poisoned");
+        self.reload_locked(&mut watch)
+    }
+
+    /// Reload the configuration while holding the watch lock.
+    fn reload_locked(&self, watch: &mut WatchState) -> Result<()> {
+        let modified = fs::metadata(&self.path)?.modified().ok();
+        let sampler = load(&self.path)?;
+        *self.sampler.write().expect("FileSampler lock poisoned") = sampler;
+        watch.last_check = Instant::now();
+        watch.modified = modified;
+        Ok(())
+    }
+
+    /// Reload the configuration if the file changed since the last check.
+    fn reload_if_changed(&self) {
+        // Skip the check if another thread is already doing it.
+        let mut watch = match self.watch.try_lock() {
+            Ok(watch) => watch,
+            Err(_) => return,
+        };
+        if watch.last_check.elapsed() < self.check_interval {
+            return;
+        }
+        watch.last_check = Instant::now();
+        let modified = fs::metadata(&self.path).and_then(|meta| meta.modified()).ok();
+        if modified != watch.modified {
+            let _ = self.reload_locked(&mut watch);
+        }
+    }
+}
+
+impl Sampler for FileSampler {
+    fn sample(&self, request: &SamplingRequest) -> SamplingDecision {
+        self.reload_if_changed();
+        let sampler = self.sampler.read().expect("FileSampler lock poisoned");
+        sampler.sample(request)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+    use std::time::Duration;
+
+    use super::super::super::tracer::TraceID;
+    use super::super::Sampler;
+    use super::super::SamplingRequest;
+    use super::FileSampler;
+
+    fn config_file(name: &str, content: &str) -> PathBuf {
+        let name = format!("opentracingrust-zipkin-{}-{}.json", name, process::id());
+        let path = env::temp_dir().join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn sampled(sampler: &FileSampler, operation_name: &str) -> bool {
+        let trace_id = TraceID::new();
+        let request = SamplingRequest::new(operation_name, &trace_id);
+        sampler.sample(&request).sampled()
+    }
+
+    #[test]
+    fn default_only() {
+        let path = config_file("default-only", r#"{"default": {"type": "const", "param": false}}"#);
+        let sampler = FileSampler::new(&path).unwrap();
+        assert!(!sampled(&sampler, "test"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn with_rules() {
+        let path = config_file("with-rules", r#"{
+            "default": {"type": "probabilistic", "param": 0},
+            "rules": [
+                {"operation": "checkout", "type": "probabilistic", "param": 1},
+                {"operation": "health*", "type": "ratelimiting", "param": 0}
+            ]
+        }"#);
+        let sampler = FileSampler::new(&path).unwrap();
+        assert!(sampled(&sampler, "checkout"));
+        assert!(!sampled(&sampler, "healthcheck"));
+        assert!(!sampled(&sampler, "test"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn invalid_config() {
+        let path = config_file("invalid", r#"{"default": {"type": "nope"}}"#);
+        let result = FileSampler::new(&path);
+        assert!(result.is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = env::temp_dir().join("opentracingrust-zipkin-does-not-exist.json");
+        assert!(FileSampler::new(path).is_err());
+    }
+
+    #[test]
+    fn explicit_reload() {
+        let path = config_file("reload", r#"{"default": {"type": "const", "param": false}}"#);
+        let sampler = FileSampler::new(&path).unwrap()
+            .check_interval(Duration::from_secs(3600));
+        assert!(!sampled(&sampler, "test"));
+        fs::write(&path, r#"{"default": {"type": "const", "param": true}}"#).unwrap();
+        sampler.reload().unwrap();
+        assert!(sampled(&sampler, "test"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn failed_reload_keeps_strategy() {
+        let path = config_file("failed-reload", r#"{"default": {"type": "const", "param": true}}"#);
+        let sampler = FileSampler::new(&path).unwrap();
+        fs::write(&path, "not json").unwrap();
+        assert!(sampler.reload().is_err());
+        assert!(sampled(&sampler, "test"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reload_on_change() {
+        let path = config_file("watch", r#"{"default": {"type": "const", "param": false}}"#);
+        let sampler = FileSampler::new(&path).unwrap()
+            .check_interval(Duration::from_secs(0));
+        assert!(!sampled(&sampler, "test"));
+        fs::write(&path, r#"{"default": {"type": "const", "param": true}}"#).unwrap();
+        // Force a different modification time in case the filesystem is too coarse.
+        sampler.watch.lock().unwrap().modified = None;
+        assert!(sampled(&sampler, "test"));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+use std::sync::Arc;
+
+use opentracingrust::TagValue;
+
+use super::tracer::TraceID;
+
+mod file;
+mod probabilistic;
+mod rate_limiting;
+mod rules;
+
+pub use self::file::FileSampler;
+pub use self::probabilistic::ProbabilisticSampler;
+pub use self::rate_limiting::RateLimitingSampler;
+pub use self::rules::RuleSampler;
+
+
+/// Details about the span a sampling decision is requested for.
+pub struct SamplingRequest<'a> {
+    operation_name: &'a str,
+    trace_id: &'a TraceID,
+}
+
+impl<'a> SamplingRequest<'a> {
+    /// Returns a new request for the given span details.
+    pub fn new(operation_name: &'a str, trace_id: &'a TraceID) -> SamplingRequest<'a> {
+        SamplingRequest {
+            operation_name,
+            trace_id,
+        }
+    }
+}
+
+impl<'a> SamplingRequest<'a> {
+    /// Access the name of the operation the span is for.
+    pub fn operation_name(&self) -> &str {
+        self.operation_name
+    }
+
+    /// Access the trace ID of the span.
+    pub fn trace_id(&self) -> &TraceID {
+        self.trace_id
+    }
+}
+
+
+/// Outcome of a `Sampler` decision.
+///
+/// Along with the sampled flag, a decision can describe the sampler that made it
+/// and carry tags to attach to the root span.
+/// The sampler type and parameter are reported to Zipkin as the `sampler.type`
+/// and `sampler.param` binary annotations of the root span.
+pub struct SamplingDecision {
+    sampled: bool,
+    sampler_param: Option<String>,
+    sampler_type: Option<String>,
+    tags: Vec<(String, TagValue)>,
+}
+
+impl SamplingDecision {
+    /// Returns a new decision without tags.
+    pub fn new(sampled: bool) -> SamplingDecision {
+        SamplingDecision {
+            sampled,
+            sampler_param: None,
+            sampler_type: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Sets the type and parameter of the sampler that made the decision.
+    pub fn sampler<P: ToString>(mut self, kind: &str, param: P) -> SamplingDecision {
+        self.sampler_param = Some(param.to_string());
+        self.sampler_type = Some(String::from(kind));
+        self
+    }
+
+    /// Adds a tag to attach to the root span.
+    pub fn tag<TV: Into<TagValue>>(mut self, tag: &str, value: TV) -> SamplingDecision {
+        self.tags.push((String::from(tag), value.into()));
+        self
+    }
+}
+
+impl SamplingDecision {
+    /// Should the trace be sampled?
+    pub fn sampled(&self) -> bool {
+        self.sampled
+    }
+
+    /// Access the parameter of the sampler that made the decision.
+    pub fn sampler_param(&self) -> Option<&str> {
+        self.sampler_param.as_ref().map(|param| &param[..])
+    }
+
+    /// Access the type of the sampler that made the decision.
+    pub fn sampler_type(&self) -> Option<&str> {
+        self.sampler_type.as_ref().map(|kind| &kind[..])
+    }
+
+    /// Access the tags to attach to the root span.
+    pub fn tags(&self) -> &[(String, TagValue)] {
+        &self.tags
+    }
+
+    /// Consumes the decision and returns the tags to attach to the root span.
+    pub fn into_tags(self) -> Vec<(String, TagValue)> {
+        self.tags
+    }
+}
+
+
+/// Decides if new traces should be sampled.
+///
+/// Samplers are only consulted for root spans: any other span
+/// inherits the sampling state from its references.
+pub trait Sampler: Send + Sync {
+    /// Decide if the trace started by the requested span should be sampled.
+    fn sample(&self, request: &SamplingRequest) -> SamplingDecision;
+}
+
+impl<S: Sampler + ?Sized> Sampler for Box<S> {
+    fn sample(&self, request: &SamplingRequest) -> SamplingDecision {
+        (**self).sample(request)
+    }
+}
+
+impl<S: Sampler + ?Sized> Sampler for Arc<S> {
+    fn sample(&self, request: &SamplingRequest) -> SamplingDecision {
+        (**self).sample(request)
+    }
+}
+
+
+/// A `Sampler` that always makes the same decision.
+pub struct ConstSampler {
+    sampled: bool,
+}
+
+impl ConstSampler {
+    /// Returns a sampler that samples every trace.
+    pub fn always() -> ConstSampler {
+        ConstSampler { sampled: true }
+    }
+
+    /// Returns a sampler that samples no trace.
+    pub fn never() -> ConstSampler {
+        ConstSampler { sampled: false }
+    }
+}
+
+impl Sampler for ConstSampler {
+    fn sample(&self, _: &SamplingRequest) -> SamplingDecision {
+        SamplingDecision::new(self.sampled).sampler("const", self.sampled)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::tracer::TraceID;
+    use super::ConstSampler;
+    use super::Sampler;
+    use super::SamplingDecision;
+    use super::SamplingRequest;
+
+    #[test]
+    fn const_sampler_always() {
+        let trace_id = TraceID::new();
+        let request = SamplingRequest::new("test", &trace_id);
+        assert!(ConstSampler::always().sample(&request).sampled());
+    }
+
+    #[test]
+    fn const_sampler_never() {
+        let trace_id = TraceID::new();
+        let request = SamplingRequest::new("test", &trace_id);
+        assert!(!ConstSampler::never().sample(&request).sampled());
+    }
+
+    #[test]
+    fn decision_tags() {
+        let decision = SamplingDecision::new(true)
+            .tag("a", "b")
+            .tag("c", true);
+        let tags: Vec<&str> = decision.tags().iter().map(|(k, _)| &k[..]).collect();
+        assert_eq!(tags, ["a", "c"]);
+    }
+
+    #[test]
+    fn decision_sampler() {
+        let decision = SamplingDecision::new(true).sampler("probabilistic", 0.5);
+        assert_eq!(decision.sampler_type(), Some("probabilistic"));
+        assert_eq!(decision.sampler_param(), Some("0.5"));
+    }
+
+    #[test]
+    fn shared_sampler() {
+        let sampler: Arc<dyn Sampler> = Arc::new(ConstSampler::never());
+        let trace_id = TraceID::new();
+        let request = SamplingRequest::new("test", &trace_id);
+        assert!(!sampler.sample(&request).sampled());
+    }
+}
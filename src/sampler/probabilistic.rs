@@ -0,0 +1,110 @@
+use super::Sampler;
+use super::SamplingDecision;
+use super::SamplingRequest;
+
+
+/// Samples a fraction of traces based on their trace ID.
+///
+/// The decision is derived from the low 64 bits of the trace ID rather than
+/// a random number so that any process rooting a span for the same trace ID
+/// makes the same decision.
+///
+/// The configured rate is recorded as the sampler parameter.
+pub struct ProbabilisticSampler {
+    boundary: u64,
+    rate: f64,
+}
+
+impl ProbabilisticSampler {
+    /// Returns a sampler that samples the given fraction of traces.
+    ///
+    /// The rate is clamped to the `[0.0, 1.0]` range.
+    pub fn new(rate: f64) -> ProbabilisticSampler {
+        let rate = rate.clamp(0.0, 1.0);
+        let boundary = (rate * u64::MAX as f64) as u64;
+        ProbabilisticSampler { boundary, rate }
+    }
+}
+
+impl ProbabilisticSampler {
+    /// Access the fraction of traces sampled.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Decide if the trace with the given low 64 bits should be sampled.
+    pub fn sample_id(&self, low: u64) -> bool {
+        match self.boundary {
+            0 => false,
+            u64::MAX => true,
+            boundary => low < boundary,
+        }
+    }
+}
+
+impl Sampler for ProbabilisticSampler {
+    fn sample(&self, request: &SamplingRequest) -> SamplingDecision {
+        let (_, low) = request.trace_id().split();
+        SamplingDecision::new(self.sample_id(low))
+            .sampler("probabilistic", self.rate)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::tracer::TraceID;
+    use super::super::Sampler;
+    use super::super::SamplingRequest;
+    use super::ProbabilisticSampler;
+
+    #[test]
+    fn rate_is_clamped() {
+        assert_eq!(ProbabilisticSampler::new(-1.0).rate(), 0.0);
+        assert_eq!(ProbabilisticSampler::new(2.0).rate(), 1.0);
+    }
+
+    #[test]
+    fn sample_all() {
+        let sampler = ProbabilisticSampler::new(1.0);
+        assert!(sampler.sample_id(0));
+        assert!(sampler.sample_id(u64::MAX));
+    }
+
+    #[test]
+    fn sample_none() {
+        let sampler = ProbabilisticSampler::new(0.0);
+        assert!(!sampler.sample_id(0));
+        assert!(!sampler.sample_id(u64::MAX));
+    }
+
+    #[test]
+    fn sample_by_low_bits() {
+        let sampler = ProbabilisticSampler::new(0.5);
+        assert!(sampler.sample_id(u64::MAX / 4));
+        assert!(!sampler.sample_id(u64::MAX / 4 * 3));
+    }
+
+    #[test]
+    fn same_trace_same_decision() {
+        let sampler = ProbabilisticSampler::new(0.5);
+        let sampled = TraceID::join(42, 1);
+        let dropped = TraceID::join(42, u64::MAX - 1);
+        for _ in 0..10 {
+            let request = SamplingRequest::new("test", &sampled);
+            assert!(sampler.sample(&request).sampled());
+            let request = SamplingRequest::new("test", &dropped);
+            assert!(!sampler.sample(&request).sampled());
+        }
+    }
+
+    #[test]
+    fn rate_is_recorded() {
+        let sampler = ProbabilisticSampler::new(0.25);
+        let trace_id = TraceID::new();
+        let request = SamplingRequest::new("test", &trace_id);
+        let decision = sampler.sample(&request);
+        assert_eq!(decision.sampler_type(), Some("probabilistic"));
+        assert_eq!(decision.sampler_param(), Some("0.25"));
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::Sampler;
+use super::SamplingDecision;
+use super::SamplingRequest;
+
+
+/// Token bucket tracking the credit available to sample traces.
+struct Bucket {
+    balance: f64,
+    last_tick: Instant,
+}
+
+
+/// Samples at most a fixed number of new traces per second.
+///
+/// The limit is enforced with a token bucket: a credit is earned at the
+/// configured rate and a trace is sampled only if a credit is available.
+/// The bucket holds at most one second worth of credits (and no less than one)
+/// so short bursts are allowed while low-traffic operations are always visible.
+/// A limit of zero samples no trace.
+///
+/// Limits are per sampler instance and are not shared across processes.
+///
+/// The configured limit is recorded as the sampler parameter.
+pub struct RateLimitingSampler {
+    bucket: Mutex<Bucket>,
+    max_balance: f64,
+    traces_per_second: f64,
+}
+
+impl RateLimitingSampler {
+    /// Returns a sampler that samples at most the given number of traces per second.
+    pub fn new(traces_per_second: f64) -> RateLimitingSampler {
+        let traces_per_second = traces_per_second.max(0.0);
+        let max_balance = match traces_per_second {
+            rate if rate > 0.0 => rate.max(1.0),
+            _ => 0.0,
+        };
+        let bucket = Bucket {
+            balance: max_balance,
+            last_tick: Instant::now(),
+        };
+        RateLimitingSampler {
+            bucket: Mutex::new(bucket),
+            max_balance,
+            traces_per_second,
+        }
+    }
+}
+
+impl RateLimitingSampler {
+    /// Access the maximum number of traces sampled each second.
+    pub fn traces_per_second(&self) -> f64 {
+        self.traces_per_second
+    }
+
+    /// Attempt to take a credit from the bucket at the given time.
+    fn take_credit(&self, now: Instant) -> bool {
+        let mut bucket = self.bucket.lock().expect("RateLimitingSampler lock poisoned");
+        if now > bucket.last_tick {
+            let elapsed = now.duration_since(bucket.last_tick);
+            let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+            let balance = bucket.balance + elapsed * self.traces_per_second;
+            bucket.balance = balance.min(self.max_balance);
+            bucket.last_tick = now;
+        }
+        if bucket.balance < 1.0 {
+            return false;
+        }
+        bucket.balance -= 1.0;
+        true
+    }
+}
+
+impl Sampler for RateLimitingSampler {
+    fn sample(&self, _: &SamplingRequest) -> SamplingDecision {
+        SamplingDecision::new(self.take_credit(Instant::now()))
+            .sampler("ratelimiting", self.traces_per_second)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use super::RateLimitingSampler;
+
+    #[test]
+    fn burst_is_limited() {
+        let sampler = RateLimitingSampler::new(2.0);
+        let now = Instant::now();
+        assert!(sampler.take_credit(now));
+        assert!(sampler.take_credit(now));
+        assert!(!sampler.take_credit(now));
+    }
+
+    #[test]
+    fn credit_is_earned_over_time() {
+        let sampler = RateLimitingSampler::new(2.0);
+        let now = Instant::now();
+        assert!(sampler.take_credit(now));
+        assert!(sampler.take_credit(now));
+        assert!(!sampler.take_credit(now));
+        let now = now + Duration::from_millis(500);
+        assert!(sampler.take_credit(now));
+        assert!(!sampler.take_credit(now));
+    }
+
+    #[test]
+    fn credit_is_capped() {
+        let sampler = RateLimitingSampler::new(2.0);
+        let now = Instant::now() + Duration::from_secs(60);
+        assert!(sampler.take_credit(now));
+        assert!(sampler.take_credit(now));
+        assert!(!sampler.take_credit(now));
+    }
+
+    #[test]
+    fn low_rates_sample_one_trace() {
+        let sampler = RateLimitingSampler::new(0.1);
+        let now = Instant::now();
+        assert!(sampler.take_credit(now));
+        assert!(!sampler.take_credit(now));
+        let now = now + Duration::from_secs(5);
+        assert!(!sampler.take_credit(now));
+        let now = now + Duration::from_secs(5);
+        assert!(sampler.take_credit(now));
+    }
+
+    #[test]
+    fn zero_rate_samples_nothing() {
+        let sampler = RateLimitingSampler::new(0.0);
+        let now = Instant::now();
+        assert!(!sampler.take_credit(now));
+        let now = now + Duration::from_secs(60);
+        assert!(!sampler.take_credit(now));
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use super::Sampler;
+use super::SamplingDecision;
+use super::SamplingRequest;
+
+
+/// Checks if an operation name matches a glob pattern.
+///
+/// The only special character supported is `*`, which matches any sequence of characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !name.starts_with(first) {
+        return false;
+    }
+    let mut rest = &name[first.len()..];
+    let mut parts: Vec<&str> = parts.collect();
+    let last = match parts.pop() {
+        // The pattern has no wildcard.
+        None => return rest.is_empty(),
+        Some(last) => last,
+    };
+    for part in parts {
+        match rest.find(part) {
+            None => return false,
+            Some(index) => rest = &rest[index + part.len()..],
+        }
+    }
+    rest.ends_with(last)
+}
+
+
+/// Samples traces with a different sampler depending on the root span operation name.
+///
+/// Rules are matched against the operation name in this order:
+///
+///   * Exact rules (patterns without a `*`).
+///   * Glob rules (patterns with at least a `*`), in the order they were added.
+///   * The default sampler if no rule matches.
+///
+/// The matched pattern is recorded on root spans with the `sampler.rule` tag.
+pub struct RuleSampler {
+    default: Box<dyn Sampler>,
+    exact: HashMap<String, Box<dyn Sampler>>,
+    globs: Vec<(String, Box<dyn Sampler>)>,
+}
+
+impl RuleSampler {
+    /// Returns a sampler with no rules that uses the given sampler by default.
+    pub fn new<S: Sampler + 'static>(default: S) -> RuleSampler {
+        RuleSampler {
+            default: Box::new(default),
+            exact: HashMap::new(),
+            globs: Vec::new(),
+        }
+    }
+
+    /// Adds a rule to sample operations matching the pattern with the given sampler.
+    ///
+    /// Patterns can be operation names (`checkout`) or globs (`health*`, `*.get`).
+    /// Adding a pattern that already exists replaces its sampler.
+    pub fn rule<S: Sampler + 'static>(mut self, pattern: &str, sampler: S) -> RuleSampler {
+        let sampler: Box<dyn Sampler> = Box::new(sampler);
+        if !pattern.contains('*') {
+            self.exact.insert(String::from(pattern), sampler);
+            return self;
+        }
+        match self.globs.iter().position(|(glob, _)| glob == pattern) {
+            Some(index) => self.globs[index].1 = sampler,
+            None => self.globs.push((String::from(pattern), sampler)),
+        };
+        self
+    }
+}
+
+impl Sampler for RuleSampler {
+    fn sample(&self, request: &SamplingRequest) -> SamplingDecision {
+        let operation_name = request.operation_name();
+        if let Some(sampler) = self.exact.get(operation_name) {
+            return sampler.sample(request).tag("sampler.rule", operation_name);
+        }
+        for (glob, sampler) in &self.globs {
+            if glob_match(glob, operation_name) {
+                return sampler.sample(request).tag("sampler.rule", &glob[..]);
+            }
+        }
+        self.default.sample(request)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use opentracingrust::TagValue;
+
+    use super::super::super::tracer::TraceID;
+    use super::super::ConstSampler;
+    use super::super::Sampler;
+    use super::super::SamplingDecision;
+    use super::super::SamplingRequest;
+    use super::RuleSampler;
+    use super::glob_match;
+
+    fn sample(sampler: &RuleSampler, operation_name: &str) -> SamplingDecision {
+        let trace_id = TraceID::new();
+        let request = SamplingRequest::new(operation_name, &trace_id);
+        sampler.sample(&request)
+    }
+
+    fn rule(decision: &SamplingDecision) -> Option<String> {
+        decision.tags().iter()
+            .find(|(tag, _)| tag == "sampler.rule")
+            .map(|(_, value)| match *value {
+                TagValue::String(ref rule) => rule.clone(),
+                _ => panic!("Sampler rule tag should be a string"),
+            })
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("checkout", "checkout"));
+        assert!(!glob_match("checkout", "checkout2"));
+        assert!(glob_match("health*", "healthcheck"));
+        assert!(glob_match("health*", "health"));
+        assert!(!glob_match("health*", "unhealthy"));
+        assert!(glob_match("*.get", "users.get"));
+        assert!(!glob_match("*.get", "users.get.all"));
+        assert!(glob_match("api.*.get", "api.users.get"));
+        assert!(!glob_match("api.*.get", "api.users.put"));
+        assert!(glob_match("*", "anything"));
+        assert!(!glob_match("a*a", "a"));
+    }
+
+    #[test]
+    fn default_when_no_match() {
+        let sampler = RuleSampler::new(ConstSampler::never())
+            .rule("checkout", ConstSampler::always());
+        let decision = sample(&sampler, "other");
+        assert!(!decision.sampled());
+        assert_eq!(rule(&decision), None);
+    }
+
+    #[test]
+    fn exact_match() {
+        let sampler = RuleSampler::new(ConstSampler::never())
+            .rule("checkout", ConstSampler::always());
+        let decision = sample(&sampler, "checkout");
+        assert!(decision.sampled());
+        assert_eq!(rule(&decision), Some(String::from("checkout")));
+    }
+
+    #[test]
+    fn exact_before_glob() {
+        let sampler = RuleSampler::new(ConstSampler::never())
+            .rule("check*", ConstSampler::never())
+            .rule("checkout", ConstSampler::always());
+        assert!(sample(&sampler, "checkout").sampled());
+        assert!(!sample(&sampler, "checkin").sampled());
+    }
+
+    #[test]
+    fn globs_in_order() {
+        let sampler = RuleSampler::new(ConstSampler::never())
+            .rule("health*", ConstSampler::always())
+            .rule("*check", ConstSampler::never());
+        let decision = sample(&sampler, "healthcheck");
+        assert!(decision.sampled());
+        assert_eq!(rule(&decision), Some(String::from("health*")));
+    }
+
+    #[test]
+    fn replace_rule() {
+        let sampler = RuleSampler::new(ConstSampler::never())
+            .rule("health*", ConstSampler::never())
+            .rule("health*", ConstSampler::always());
+        assert!(sample(&sampler, "healthcheck").sampled());
+    }
+}
@@ -0,0 +1,196 @@
+use std::fmt::Write;
+
+
+/// Checks if a byte can be used in a baggage key.
+///
+/// Keys are HTTP tokens as defined by RFC 7230.
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$&'*+-.^_`|~".contains(&byte)
+}
+
+/// Checks if a string is a valid baggage key.
+fn is_key(key: &str) -> bool {
+    !key.is_empty() && key.bytes().all(is_token)
+}
+
+/// Checks if a byte can be used in a baggage value without escaping.
+///
+/// Values can use any printable ASCII character except `"`, `,`, `;`, `\\`
+/// and `%`, which is reserved for escapes.
+fn is_value_char(byte: u8) -> bool {
+    byte.is_ascii_graphic() && !b"\",;\\%".contains(&byte)
+}
+
+/// Checks if a byte can be used in a text map key without escaping.
+///
+/// Only lowercase characters are allowed so keys survive carriers that change their case.
+fn is_text_map_key_char(byte: u8) -> bool {
+    byte.is_ascii_lowercase() || byte.is_ascii_digit() || b"-_.".contains(&byte)
+}
+
+/// Checks if a byte is unreserved according to RFC 3986.
+fn is_unreserved(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte)
+}
+
+/// Percent-encode all bytes that are not safe according to the given function.
+fn percent_encode(value: &str, safe: fn(u8) -> bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if safe(byte) {
+            encoded.push(byte as char);
+        } else {
+            write!(encoded, "%{:02X}", byte).expect("writing to a String can't fail");
+        }
+    }
+    encoded
+}
+
+/// Decode percent-encoded bytes, returning `None` if the result is not valid UTF-8.
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escape = match bytes[index] {
+            b'%' => value.get(index + 1..index + 3)
+                .filter(|hex| hex.bytes().all(|c| c.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+
+/// Percent-encode a single baggage value, for formats with a header per item.
+pub fn encode_value(value: &str) -> String {
+    percent_encode(value, is_value_char)
+}
+
+/// Decode a single percent-encoded baggage value.
+pub fn decode_value(value: &str) -> Option<String> {
+    percent_decode(value)
+}
+
+
+/// Percent-encode a baggage key for use in text map keys.
+///
+/// The encoded key only uses lowercase characters, including in escapes.
+pub fn encode_text_map_key(key: &str) -> String {
+    percent_encode(key, is_text_map_key_char).to_ascii_lowercase()
+}
+
+/// Percent-encode a baggage value for use in text map values.
+pub fn encode_text_map_value(value: &str) -> String {
+    percent_encode(value, is_unreserved)
+}
+
+
+/// Encode baggage items into the value of a W3C `baggage` header.
+///
+/// Values are percent-encoded where needed but keys can't be escaped:
+/// items with keys that are not HTTP tokens are skipped.
+/// Items are sorted by key so the header is stable.
+/// See https://www.w3.org/TR/baggage/
+pub fn encode<'a, I>(items: I) -> Option<String>
+    where I: IntoIterator<Item=(&'a String, &'a String)>
+{
+    let mut items: Vec<(&String, &String)> = items.into_iter().collect();
+    items.sort();
+    let members: Vec<String> = items.into_iter()
+        .filter(|(key, _)| is_key(key))
+        .map(|(key, value)| format!("{}={}", key, percent_encode(value, is_value_char)))
+        .collect();
+    match members.is_empty() {
+        true => None,
+        false => Some(members.join(",")),
+    }
+}
+
+/// Decode baggage items from the value of a W3C `baggage` header.
+///
+/// Member properties (anything after a `;`) are ignored.
+/// Invalid members, including members with keys that are not HTTP tokens,
+/// are skipped so that a single bad item does not cause all the baggage to be lost.
+pub fn decode(header: &str) -> Vec<(String, String)> {
+    header.split(',').filter_map(|member| {
+        let member = member.split(';').next().unwrap_or("");
+        let mut parts = member.splitn(2, '=');
+        let key = parts.next()?.trim();
+        let value = parts.next()?.trim();
+        if !is_key(key) {
+            return None;
+        }
+        Some((String::from(key), percent_decode(value)?))
+    }).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::decode;
+    use super::decode_value;
+    use super::encode;
+    use super::encode_text_map_key;
+    use super::encode_text_map_value;
+
+    #[test]
+    fn encode_items() {
+        let mut items: BTreeMap<String, String> = BTreeMap::new();
+        items.insert(String::from("user"), String::from("alice"));
+        items.insert(String::from("note"), String::from("a, b;c=d%"));
+        items.insert(String::from("not a token"), String::from("skipped"));
+        assert_eq!(encode(&items).unwrap(), "note=a%2C%20b%3Bc=d%25,user=alice");
+    }
+
+    #[test]
+    fn encode_nothing() {
+        let items: BTreeMap<String, String> = BTreeMap::new();
+        assert_eq!(encode(&items), None);
+    }
+
+    #[test]
+    fn decode_items() {
+        let items = decode("user=alice, note = a%2C%20b%3Bc=d%25;prop=1;flag , bad, =x, a%20b=c");
+        assert_eq!(items, vec![
+            (String::from("user"), String::from("alice")),
+            (String::from("note"), String::from("a, b;c=d%")),
+        ]);
+    }
+
+    #[test]
+    fn decode_unicode() {
+        let items = decode("city=Z%C3%BCrich");
+        assert_eq!(items, vec![(String::from("city"), String::from("Zürich"))]);
+    }
+
+    #[test]
+    fn text_map_encoding() {
+        assert_eq!(encode_text_map_key("User.Name"), "%55ser.%4eame");
+        assert_eq!(encode_text_map_key("a/b"), "a%2fb");
+        assert_eq!(encode_text_map_value("a b,c=d"), "a%20b%2Cc%3Dd");
+        assert_eq!(decode_value("%55ser.%4eame").unwrap(), "User.Name");
+    }
+
+    #[test]
+    fn decode_invalid_escapes() {
+        let items = decode("a=100%,b=%zz,c=%FF");
+        assert_eq!(items, vec![
+            (String::from("a"), String::from("100%")),
+            (String::from("b"), String::from("%zz")),
+        ]);
+    }
+}
@@ -1,15 +1,52 @@
+use std::cell::Cell;
+use std::cell::RefCell;
+
 use rand::random;
 
+use opentracingrust::ImplContextBox;
+use opentracingrust::SpanContext;
 use opentracingrust::SpanReference;
 use opentracingrust::SpanReferenceAware;
 
+use super::super::sampler::SamplingDecision;
 use super::trace_id::TraceID;
+use super::trace_id::ZERO_TRACE_ID;
+
+
+/// Sampling state of a trace.
+///
+/// Follows the B3 specification where the sampling decision can be
+/// accepted, denied or deferred to the receiving service.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplingState {
+    /// The trace is sampled.
+    Accept,
+
+    /// The decision is left to the next service in the trace.
+    Defer,
+
+    /// The trace is not sampled.
+    Deny,
+}
+
+impl From<bool> for SamplingState {
+    fn from(sampled: bool) -> SamplingState {
+        match sampled {
+            true => SamplingState::Accept,
+            false => SamplingState::Deny,
+        }
+    }
+}
 
 
 /// Zipkin specific `SpanContext`.
 ///
 /// Carries information about the current trace.
 ///
+/// Contexts extracted without a trace ID or span ID, such as from B3 sampling-only
+/// headers, only carry the rest of the trace information: spans referencing them
+/// are root spans and use the trace ID of the span, if the context has none.
+///
 /// # References
 ///
 ///   * https://zipkin.io/pages/instrumenting.html#communicating-trace-information
@@ -17,29 +54,83 @@ use super::trace_id::TraceID;
 #[derive(Clone)]
 pub struct ZipkinContext {
     debug: bool,
+    join: bool,
     parent_span_id: Option<u64>,
-    sampled: bool,
-    span_id: u64,
-    trace_id: TraceID,
+    sampler: RefCell<Option<(String, String)>>,
+    sampling: Cell<SamplingState>,
+    shared: bool,
+    span_id: Option<u64>,
+    trace_id: Option<TraceID>,
+    trace_state: Option<String>,
 }
 
 impl ZipkinContext {
     /// Returns a new context with the default options.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> ZipkinContext {
         ZipkinContext::new_with_options(ZipkinContextOptions::default())
     }
 
     /// Returns a new context with the given options.
+    ///
+    /// Random span and trace IDs are generated if they are not set in the options.
     pub fn new_with_options(options: ZipkinContextOptions) -> ZipkinContext {
         let span_id = options.span_id.unwrap_or_else(random::<u64>);
         let trace_id = options.trace_id.unwrap_or_else(TraceID::new);
+        let options = options.span_id(span_id).trace_id(trace_id);
+        ZipkinContext::new_partial(options)
+    }
+
+    /// Returns a new context with the given options, without generating missing IDs.
+    ///
+    /// Used for extracted contexts that only carry sampling flags or a trace ID.
+    pub(crate) fn new_partial(options: ZipkinContextOptions) -> ZipkinContext {
         ZipkinContext {
             debug: options.debug,
+            join: false,
             parent_span_id: options.parent_span_id,
-            sampled: options.sampled,
-            span_id,
-            trace_id,
+            sampler: RefCell::new(None),
+            sampling: Cell::new(options.sampling),
+            shared: options.shared,
+            span_id: options.span_id,
+            trace_id: options.trace_id,
+            trace_state: options.trace_state,
+        }
+    }
+
+    /// Returns a copy of an extracted context for an RPC server span to join.
+    ///
+    /// Spans started as `ChildOf` the returned context reuse its span ID and
+    /// parent span ID instead of starting a new span and are reported as shared.
+    /// This is the classic Zipkin model where RPC clients and servers share a span.
+    ///
+    /// Only one span may join a context: other spans, such as the server's children,
+    /// should reference the original context or the server span instead.
+    /// Contexts without a span ID can't be joined and start a new span.
+    pub fn join(context: &SpanContext) -> SpanContext {
+        ZipkinContext::update(context, |inner| inner.join = true)
+    }
+
+    /// Returns a copy of a context with the trace ID left-padded to 16 bytes.
+    pub(crate) fn pad_trace_id(context: &SpanContext) -> SpanContext {
+        ZipkinContext::update(context, |inner| {
+            inner.trace_id = inner.trace_id.map(|trace_id| trace_id.padded());
+        })
+    }
+
+    /// Returns a copy of a context, and its baggage, updated by the given function.
+    fn update<F>(context: &SpanContext, update: F) -> SpanContext
+        where F: FnOnce(&mut ZipkinContext)
+    {
+        let mut inner = context.impl_context::<ZipkinContext>().expect(
+            "Invalid SpanContext, was it created by ZipkinTracer?"
+        ).clone();
+        update(&mut inner);
+        let mut updated = SpanContext::new(ImplContextBox::new(inner));
+        for (key, value) in context.baggage_items() {
+            updated.set_baggage_item(key.clone(), value.clone());
         }
+        updated
     }
 }
 
@@ -55,18 +146,78 @@ impl ZipkinContext {
     }
 
     /// Is the context sampled?
+    ///
+    /// Contexts that deferred the sampling decision are not sampled.
     pub fn sampled(&self) -> bool {
-        self.sampled
+        self.sampling.get() == SamplingState::Accept
+    }
+
+    /// Access the context's sampling state.
+    pub fn sampling_state(&self) -> SamplingState {
+        self.sampling.get()
+    }
+
+    /// Access the type and parameter of the sampler that decided on this span.
+    ///
+    /// Only spans the tracer made a sampling decision for have this information.
+    pub fn sampler(&self) -> Option<(String, String)> {
+        self.sampler.borrow().clone()
+    }
+
+    /// Records the sampling decision the tracer made for this span.
+    ///
+    /// Root spans are only known once their references are set so the
+    /// decision is recorded after the context is attached to a span.
+    pub(crate) fn record_decision(&self, decision: &SamplingDecision) {
+        self.sampling.set(SamplingState::from(decision.sampled()));
+        let sampler = match (decision.sampler_type(), decision.sampler_param()) {
+            (Some(kind), Some(param)) => Some((String::from(kind), String::from(param))),
+            _ => None,
+        };
+        *self.sampler.borrow_mut() = sampler;
+    }
+
+    /// Is the span shared with the RPC client that started it?
+    ///
+    /// Shared spans are started as children of a context returned by `ZipkinContext::join`.
+    pub fn shared(&self) -> bool {
+        self.shared
     }
 
     /// Access the context's span ID.
+    ///
+    /// Extracted contexts without a span ID return `0`: use `known_span_id` to detect them.
     pub fn span_id(&self) -> u64 {
-        self.span_id
+        self.span_id.unwrap_or(0)
     }
 
     /// Access the context's trace ID.
+    ///
+    /// Extracted contexts without a trace ID return a zero trace ID:
+    /// use `known_trace_id` to detect them.
     pub fn trace_id(&self) -> &TraceID {
-        &self.trace_id
+        self.trace_id.as_ref().unwrap_or(&ZERO_TRACE_ID)
+    }
+
+    /// Access the W3C `tracestate` received with the context, if any.
+    ///
+    /// The trace state is not used by Zipkin but is propagated to child spans.
+    pub fn trace_state(&self) -> Option<&str> {
+        self.trace_state.as_ref().map(|state| &state[..])
+    }
+
+    /// Access the context's span ID, if it has one.
+    ///
+    /// Only extracted contexts that carry sampling flags or a trace ID lack a span ID.
+    pub fn known_span_id(&self) -> Option<u64> {
+        self.span_id
+    }
+
+    /// Access the context's trace ID, if it has one.
+    ///
+    /// Only extracted contexts that carry sampling flags lack a trace ID.
+    pub fn known_trace_id(&self) -> Option<&TraceID> {
+        self.trace_id.as_ref()
     }
 }
 
@@ -76,16 +227,27 @@ impl SpanReferenceAware for ZipkinContext {
             SpanReference::ChildOf(ref context) => {
                 let context = context.impl_context::<ZipkinContext>().unwrap();
                 self.debug = context.debug;
-                self.parent_span_id = Some(context.span_id);
-                self.sampled = context.sampled;
-                self.trace_id = context.trace_id.clone();
+                self.parent_span_id = context.span_id;
+                *self.sampler.borrow_mut() = None;
+                self.sampling.set(context.sampling.get());
+                self.shared = false;
+                if context.join && context.span_id.is_some() {
+                    self.parent_span_id = context.parent_span_id;
+                    self.shared = true;
+                    self.span_id = context.span_id;
+                }
+                self.trace_id = context.trace_id.or(self.trace_id);
+                self.trace_state = context.trace_state.clone();
             }
             SpanReference::FollowsFrom(ref context) => {
                 let context = context.impl_context::<ZipkinContext>().unwrap();
                 self.debug = context.debug;
                 self.parent_span_id = None;
-                self.sampled = context.sampled;
-                self.trace_id = context.trace_id.clone();
+                *self.sampler.borrow_mut() = None;
+                self.sampling.set(context.sampling.get());
+                self.shared = false;
+                self.trace_id = context.trace_id.or(self.trace_id);
+                self.trace_state = context.trace_state.clone();
             }
         }
     }
@@ -96,9 +258,11 @@ impl SpanReferenceAware for ZipkinContext {
 pub struct ZipkinContextOptions {
     debug: bool,
     parent_span_id: Option<u64>,
-    sampled: bool,
+    sampling: SamplingState,
+    shared: bool,
     span_id: Option<u64>,
     trace_id: Option<TraceID>,
+    trace_state: Option<String>,
 }
 
 impl ZipkinContextOptions {
@@ -116,7 +280,19 @@ impl ZipkinContextOptions {
 
     /// Sets the desired sampling flag.
     pub fn sampled(mut self, sampled: bool) -> ZipkinContextOptions {
-        self.sampled = sampled;
+        self.sampling = SamplingState::from(sampled);
+        self
+    }
+
+    /// Sets the desired sampling state.
+    pub fn sampling_state(mut self, sampling: SamplingState) -> ZipkinContextOptions {
+        self.sampling = sampling;
+        self
+    }
+
+    /// Sets the desired shared flag.
+    pub fn shared(mut self, shared: bool) -> ZipkinContextOptions {
+        self.shared = shared;
         self
     }
 
@@ -131,6 +307,12 @@ impl ZipkinContextOptions {
         self.trace_id = Some(trace_id);
         self
     }
+
+    /// Sets the W3C trace state to propagate.
+    pub fn trace_state(mut self, trace_state: String) -> ZipkinContextOptions {
+        self.trace_state = Some(trace_state);
+        self
+    }
 }
 
 impl Default for ZipkinContextOptions {
@@ -138,9 +320,11 @@ impl Default for ZipkinContextOptions {
         ZipkinContextOptions {
             debug: false,
             parent_span_id: None,
-            sampled: true,
+            sampling: SamplingState::Accept,
+            shared: false,
             span_id: None,
             trace_id: None,
+            trace_state: None,
         }
     }
 }
@@ -149,6 +333,7 @@ impl Default for ZipkinContextOptions {
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
+    use super::SamplingState;
     use super::TraceID;
     use super::ZipkinContext;
     use super::ZipkinContextOptions;
@@ -157,7 +342,7 @@ mod tests {
     fn new_span_with_defaults() {
         let context = ZipkinContext::new();
         assert_eq!(context.debug, false);
-        assert_eq!(context.sampled, true);
+        assert_eq!(context.sampled(), true);
     }
 
     #[test]
@@ -169,14 +354,38 @@ mod tests {
             .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap());
         let context = ZipkinContext::new_with_options(options);
         assert_eq!(context.debug, true);
-        assert_eq!(context.sampled, false);
-        assert_eq!(context.span_id, 42);
+        assert_eq!(context.sampled(), false);
+        assert_eq!(context.span_id, Some(42));
         assert_eq!(
             context.trace_id,
-            TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap()
+            Some(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap())
         );
     }
 
+    #[test]
+    fn new_span_with_random_ids() {
+        let context = ZipkinContext::new();
+        assert!(context.known_span_id().is_some());
+        assert!(context.known_trace_id().is_some());
+    }
+
+    #[test]
+    fn new_partial_span_without_ids() {
+        let context = ZipkinContext::new_partial(ZipkinContextOptions::default());
+        assert_eq!(context.span_id, None);
+        assert_eq!(context.trace_id, None);
+        assert_eq!(context.span_id(), 0);
+        assert_eq!(context.trace_id().split(), (0, 0));
+    }
+
+    #[test]
+    fn deferred_span_is_not_sampled() {
+        let options = ZipkinContextOptions::default().sampling_state(SamplingState::Defer);
+        let context = ZipkinContext::new_with_options(options);
+        assert_eq!(context.sampling_state(), SamplingState::Defer);
+        assert!(!context.sampled());
+    }
+
     mod references {
         use std::str::FromStr;
         use opentracingrust::ImplContextBox;
@@ -184,6 +393,7 @@ mod tests {
         use opentracingrust::SpanReference;
         use opentracingrust::SpanReferenceAware;
 
+        use super::SamplingState;
         use super::TraceID;
         use super::ZipkinContext;
         use super::ZipkinContextOptions;
@@ -203,10 +413,10 @@ mod tests {
             let mut context = ZipkinContext::new();
             context.reference_span(&child_of);
             assert_eq!(context.debug, true);
-            assert_eq!(context.sampled, false);
+            assert_eq!(context.sampled(), false);
             assert_eq!(
                 context.trace_id,
-                TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap()
+                Some(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap())
             );
         }
 
@@ -217,11 +427,59 @@ mod tests {
             let mut context = ZipkinContext::new();
             context.reference_span(&follows_from);
             assert_eq!(context.debug, true);
-            assert_eq!(context.sampled, false);
+            assert_eq!(context.sampled(), false);
             assert_eq!(
                 context.trace_id,
-                TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap()
+                Some(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap())
             );
         }
+
+        #[test]
+        fn child_of_joined_shares_the_span() {
+            let options = ZipkinContextOptions::default().parent_span_id(1).span_id(2);
+            let parent = ZipkinContext::new_with_options(options);
+            let parent = SpanContext::new(ImplContextBox::new(parent));
+            let child_of = SpanReference::ChildOf(ZipkinContext::join(&parent));
+            let mut context = ZipkinContext::new();
+            context.reference_span(&child_of);
+            assert_eq!(context.parent_span_id, Some(1));
+            assert_eq!(context.span_id, Some(2));
+            assert!(context.shared());
+
+            let child_of = SpanContext::new(ImplContextBox::new(context));
+            let child_of = SpanReference::ChildOf(child_of);
+            let mut context = ZipkinContext::new();
+            context.reference_span(&child_of);
+            assert_eq!(context.parent_span_id, Some(2));
+            assert!(!context.shared());
+        }
+
+        #[test]
+        fn child_of_context_without_ids_is_root() {
+            let options = ZipkinContextOptions::default().debug(true);
+            let child_of = ZipkinContext::new_partial(options);
+            let child_of = SpanContext::new(ImplContextBox::new(child_of));
+            let child_of = SpanReference::ChildOf(child_of);
+            let options = ZipkinContextOptions::default()
+                .span_id(42)
+                .trace_id(TraceID::join(1, 2));
+            let mut context = ZipkinContext::new_with_options(options);
+            context.reference_span(&child_of);
+            assert!(context.debug());
+            assert_eq!(context.parent_span_id(), None);
+            assert_eq!(context.span_id(), 42);
+            assert_eq!(context.trace_id(), &TraceID::join(1, 2));
+        }
+
+        #[test]
+        fn child_of_deferred_is_deferred() {
+            let options = ZipkinContextOptions::default().sampling_state(SamplingState::Defer);
+            let child_of = ZipkinContext::new_with_options(options);
+            let child_of = SpanContext::new(ImplContextBox::new(child_of));
+            let child_of = SpanReference::ChildOf(child_of);
+            let mut context = ZipkinContext::new();
+            context.reference_span(&child_of);
+            assert_eq!(context.sampling_state(), SamplingState::Defer);
+        }
     }
 }
@@ -1,5 +1,9 @@
+use std::io::Cursor;
 use std::io::Read;
 
+use byteorder::NetworkEndian;
+use byteorder::ReadBytesExt;
+
 use opentracingrust::Error;
 use opentracingrust::ImplContextBox;
 use opentracingrust::MapCarrier;
@@ -7,25 +11,81 @@ use opentracingrust::Result;
 use opentracingrust::SpanContext;
 
 use thrift::protocol::TBinaryInputProtocol;
+use thrift::protocol::TCompactInputProtocol;
 use thrift::transport::TBufferedReadTransport;
 
+use super::baggage;
+use super::context::SamplingState;
 use super::context::ZipkinContext;
 use super::context::ZipkinContextOptions;
 
 use super::error::data_encoding_error;
 use super::error::thrift_error;
+use super::propagator::ThriftProtocol;
+use super::propagator::BINARY_MAGIC;
+use super::propagator::BINARY_VERSION;
 use super::trace_id::TraceID;
 
 use super::super::thrift_gen::binary_format;
 
 
 /// Decode the SpanContext from a thrift structure.
-pub fn binary(carrier: Box<&mut dyn Read>) -> Result<Option<SpanContext>> {
+pub fn binary(carrier: &mut dyn Read) -> Result<Option<SpanContext>> {
     let transport = TBufferedReadTransport::new(carrier);
     let mut protocol = TBinaryInputProtocol::new(transport, true);
     let result = binary_format::SpanContext::read_from_in_protocol(&mut protocol);
     let message = result.map_err(thrift_error)?;
+    from_thrift(message)
+}
+
+
+/// Decode the SpanContext from a thrift structure preceded by a version header.
+///
+/// The header is described in `inject::versioned_binary`.
+/// Unknown versions and protocols are rejected with an error.
+/// Carriers without the header are decoded as plain thrift binary structures
+/// if `accept_unversioned` is set and rejected otherwise.
+/// An empty carrier has no context.
+pub fn versioned_binary(
+    carrier: &mut dyn Read, accept_unversioned: bool
+) -> Result<Option<SpanContext>> {
+    let mut buffer = Vec::new();
+    carrier.read_to_end(&mut buffer)?;
+    if buffer.is_empty() {
+        return Ok(None);
+    }
+    if buffer.len() < 4 || buffer[..2] != BINARY_MAGIC {
+        if !accept_unversioned {
+            return Err(Error::Msg(String::from(
+                "Binary carrier does not have a versioned Zipkin context"
+            )));
+        }
+        let mut cursor = Cursor::new(buffer);
+        return binary(&mut cursor);
+    }
+    if buffer[2] != BINARY_VERSION {
+        return Err(Error::Msg(format!("Unsupported binary context version {}", buffer[2])));
+    }
+
+    let mut cursor = Cursor::new(&buffer[4..]);
+    let transport = TBufferedReadTransport::new(&mut cursor);
+    let result = match ThriftProtocol::from_id(buffer[3]) {
+        Some(ThriftProtocol::Binary) => {
+            let mut protocol = TBinaryInputProtocol::new(transport, true);
+            binary_format::SpanContext::read_from_in_protocol(&mut protocol)
+        }
+        Some(ThriftProtocol::Compact) => {
+            let mut protocol = TCompactInputProtocol::new(transport);
+            binary_format::SpanContext::read_from_in_protocol(&mut protocol)
+        }
+        None => return Err(Error::Msg(format!("Unsupported thrift protocol {}", buffer[3]))),
+    };
+    from_thrift(result.map_err(thrift_error)?)
+}
+
 
+/// Build the SpanContext from a decoded thrift structure.
+fn from_thrift(message: binary_format::SpanContext) -> Result<Option<SpanContext>> {
     // Decode the TraceID from a (low, high) tuple.
     let trace_id_low = message.trace_id.ok_or(
         Error::Msg(String::from("Decoded context does not have a TraceID (low)"))
@@ -49,14 +109,11 @@ pub fn binary(carrier: Box<&mut dyn Read>) -> Result<Option<SpanContext>> {
         Some(parent_span_id) => options.parent_span_id(parent_span_id as u64),
     };
     let options = match message.sampled {
-        None => {
-            // TODO: once Samplers are implemented, call it here?
-            options.sampled(true)
-        },
+        None => options.sampling_state(SamplingState::Defer),
         Some(sampled) => options.sampled(sampled),
     };
 
-    let context = ZipkinContext::new_with_options(options);
+    let context = ZipkinContext::new_partial(options);
     let context = ImplContextBox::new(context);
     let mut context = SpanContext::new(context);
 
@@ -69,58 +126,494 @@ pub fn binary(carrier: Box<&mut dyn Read>) -> Result<Option<SpanContext>> {
 }
 
 
-/// Decode the SpanContext from HTTP Headers.
+/// Decode the SpanContext from the gRPC `grpc-trace-bin` binary format.
+///
+/// The format is the OpenCensus binary layout described in `inject::grpc_trace_bin`.
+/// Only version `0` is supported and fields must be in order: unknown fields
+/// after the known ones are ignored, as required by the specification.
+/// An empty carrier has no context.
+///
+/// Contexts are sampled if bit `1` of the trace options is set.
+/// A missing trace options field means the context is not sampled.
+pub fn grpc_trace_bin(carrier: &mut dyn Read) -> Result<Option<SpanContext>> {
+    let mut buffer = Vec::new();
+    carrier.read_to_end(&mut buffer)?;
+    if buffer.is_empty() {
+        return Ok(None);
+    }
+    if buffer[0] != 0 {
+        return Err(Error::Msg(format!("Unsupported grpc-trace-bin version {}", buffer[0])));
+    }
+    let invalid = || Error::Msg(String::from("Invalid grpc-trace-bin context"));
+    if buffer.len() < 27 || buffer[1] != 0 || buffer[18] != 1 {
+        return Err(invalid());
+    }
+    let mut cursor = Cursor::new(&buffer[2..18]);
+    let high = cursor.read_u64::<NetworkEndian>()?;
+    let low = cursor.read_u64::<NetworkEndian>()?;
+    let span_id = Cursor::new(&buffer[19..27]).read_u64::<NetworkEndian>()?;
+    let sampled = match buffer.get(27) {
+        Some(2) => buffer.get(28).ok_or_else(invalid)? & 0x01 == 0x01,
+        _ => false,
+    };
+
+    let options = ZipkinContextOptions::default()
+        .sampled(sampled)
+        .span_id(span_id)
+        .trace_id(TraceID::join(high, low));
+    let context = ZipkinContext::new_partial(options);
+    Ok(Some(SpanContext::new(ImplContextBox::new(context))))
+}
+
+
+/// Decode the SpanContext from the multiple `X-B3-*` HTTP Headers.
+///
+/// The decoding is done following the B3 propagation format.
+/// See https://github.com/openzipkin/b3-propagation
+///
+/// Baggage items are decoded as described in `with_baggage_headers`.
+pub fn b3_multi_headers(carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+    let options = b3_multi_options(carrier)?;
+    Ok(options.map(|options| with_baggage_headers(options, carrier)))
+}
+
+
+/// Decode the SpanContext from the single `b3` HTTP Header.
 ///
 /// The decoding is done following the B3 propagation format.
 /// See https://github.com/openzipkin/b3-propagation
 ///
-/// Baggage items are expected to be in the format `OT-Baggage-{Key}: {Value}`.
-pub fn http_headers(carrier: Box<&dyn MapCarrier>) -> Result<Option<SpanContext>> {
-    // Trace ID.
-    let trace_id = match carrier.get("X-B3-TraceId") {
+/// Baggage items are decoded as described in `with_baggage_headers`.
+pub fn b3_single_header(carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+    let options = match find_header(carrier, "b3") {
+        Some(header) => b3_single_options(&header)?,
+        None => return Ok(None),
+    };
+    Ok(Some(with_baggage_headers(options, carrier)))
+}
+
+
+/// Decode the SpanContext from the W3C `traceparent` and `tracestate` HTTP Headers.
+///
+/// See https://www.w3.org/TR/trace-context/
+///
+/// Baggage items are decoded as described in `with_baggage_headers`.
+pub fn trace_context(carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+    let options = trace_context_options(carrier)?;
+    Ok(options.map(|options| with_baggage_headers(options, carrier)))
+}
+
+
+/// Decode the SpanContext from the Jaeger `uber-trace-id` HTTP Header.
+///
+/// The header has the format `{trace-id}:{span-id}:{parent-span-id}:{flags}`
+/// and can be percent-encoded.
+/// Identifiers may omit leading zeros and a parent span ID of `0` means no parent.
+/// Flags bit `1` marks the context as sampled and bit `2` as debug (which implies sampled).
+/// See https://www.jaegertracing.io/docs/latest/client-libraries/#propagation-format
+///
+/// Baggage items are read from `uberctx-{Key}: {Value}` headers,
+/// with values percent-decoded.
+pub fn jaeger(carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+    let header = match find_header(carrier, "uber-trace-id") {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let invalid = || Error::Msg(format!("Invalid uber-trace-id header '{}'", header));
+    let decoded = baggage::decode_value(header.trim()).ok_or_else(invalid)?;
+    let fields: Vec<&str> = decoded.split(':').collect();
+    if fields.len() != 4 || fields.iter().any(|field| !is_hex(field, 1, 32)) {
+        return Err(invalid());
+    }
+    let (trace_id, span_id, parent_span_id, flags) = (fields[0], fields[1], fields[2], fields[3]);
+    if !is_hex(span_id, 1, 16) || !is_hex(parent_span_id, 1, 16) || !is_hex(flags, 1, 2) {
+        return Err(invalid());
+    }
+    let width = if trace_id.len() <= 16 { 16 } else { 32 };
+    let trace_id: TraceID = format!("{:0>width$}", trace_id, width = width).parse()
+        .map_err(data_encoding_error)?;
+    let span_id = u64::from_str_radix(span_id, 16)?;
+    let parent_span_id = u64::from_str_radix(parent_span_id, 16)?;
+    let flags = u8::from_str_radix(flags, 16)?;
+
+    let debug = flags & 0x02 == 0x02;
+    let options = ZipkinContextOptions::default()
+        .debug(debug)
+        .sampled(debug || flags & 0x01 == 0x01)
+        .span_id(span_id)
+        .trace_id(trace_id);
+    let options = match parent_span_id {
+        0 => options,
+        parent_span_id => options.parent_span_id(parent_span_id),
+    };
+
+    let context = ZipkinContext::new_partial(options);
+    let context = ImplContextBox::new(context);
+    let mut context = SpanContext::new(context);
+    for (key, value) in carrier.items() {
+        let prefix = key.get(..8).unwrap_or("");
+        if prefix.eq_ignore_ascii_case("uberctx-") {
+            let value = baggage::decode_value(value).unwrap_or_else(|| value.clone());
+            context.set_baggage_item(String::from(&key[8..]), value);
+        }
+    }
+    Ok(Some(context))
+}
+
+
+/// Decode the SpanContext from the AWS X-Ray `X-Amzn-Trace-Id` HTTP Header.
+///
+/// The header has the format `Root={root};Parent={parent-id};Sampled={0|1|?}`
+/// where the root trace ID is `1-{epoch}-{unique-id}`: the 8 hex characters of the
+/// epoch and the 24 hex characters of the unique ID are joined into a 128-bit trace ID.
+/// Other fields are ignored.
+///
+/// Load balancers only set the root trace ID: when the `Parent` field is missing
+/// the context has no span ID and spans started from it are root spans of the trace.
+/// The sampling decision is deferred if `Sampled` is missing or is `?`.
+/// See https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader
+///
+/// Baggage items are decoded as described in `with_baggage_headers`.
+pub fn xray(carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+    let header = match find_header(carrier, "X-Amzn-Trace-Id") {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let invalid = || Error::Msg(format!("Invalid X-Amzn-Trace-Id header '{}'", header));
+    let mut root = None;
+    let mut parent = None;
+    let mut sampled = None;
+    for field in header.split(';') {
+        let mut parts = field.splitn(2, '=');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().map(str::trim);
+        match key {
+            "Root" => root = value,
+            "Parent" => parent = value,
+            "Sampled" => sampled = value,
+            _ => (),
+        };
+    }
+
+    let root = root.ok_or_else(invalid)?;
+    let fields: Vec<&str> = root.split('-').collect();
+    if fields.len() != 3 || fields[0] != "1" {
+        return Err(invalid());
+    }
+    if !is_hex(fields[1], 8, 8) || !is_hex(fields[2], 24, 24) {
+        return Err(invalid());
+    }
+    let trace_id: TraceID = format!("{}{}", fields[1], fields[2]).parse()
+        .map_err(data_encoding_error)?;
+    let options = ZipkinContextOptions::default().trace_id(trace_id);
+    let options = match parent {
+        None => options,
+        Some(parent) if is_hex(parent, 16, 16) => options.span_id(u64::from_str_radix(parent, 16)?),
+        Some(_) => return Err(invalid()),
+    };
+    let options = match sampled {
+        None | Some("?") => options.sampling_state(SamplingState::Defer),
+        Some("1") => options.sampled(true),
+        Some("0") => options.sampled(false),
+        Some(_) => return Err(invalid()),
+    };
+    Ok(Some(with_baggage_headers(options, carrier)))
+}
+
+
+/// Decode the SpanContext from a text map encoded by `inject::text_map`.
+///
+/// Context keys are decoded as the multiple B3 headers and baggage items are read
+/// from `ot-baggage-{key}: {value}` items with percent-encoded keys and values.
+pub fn text_map(carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+    let options = match b3_multi_options(carrier)? {
+        Some(options) => options,
+        None => return Ok(None),
+    };
+    let context = ZipkinContext::new_partial(options);
+    let context = ImplContextBox::new(context);
+    let mut context = SpanContext::new(context);
+    for (key, value) in carrier.items() {
+        let prefix = key.get(..11).unwrap_or("");
+        if !prefix.eq_ignore_ascii_case("ot-baggage-") {
+            continue;
+        }
+        let invalid = || Error::Msg(format!("Invalid text map baggage item '{}'", key));
+        let name = baggage::decode_value(&key[11..]).ok_or_else(invalid)?;
+        let value = baggage::decode_value(value).ok_or_else(invalid)?;
+        context.set_baggage_item(name, value);
+    }
+    Ok(Some(context))
+}
+
+
+/// Look up an HTTP Header ignoring the case of its name.
+///
+/// HTTP header names are case-insensitive and many frameworks (and HTTP/2)
+/// lowercase them, so carriers are not required to normalise keys.
+/// The exact name is tried first to avoid a scan of all the items.
+fn find_header(carrier: &dyn MapCarrier, name: &str) -> Option<String> {
+    if let Some(value) = carrier.get(name) {
+        return Some(value);
+    }
+    carrier.items().into_iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.clone())
+}
+
+
+/// Build a SpanContext and decode baggage items from HTTP Headers.
+///
+/// Baggage items are read from the W3C `baggage` header and from
+/// `OT-Baggage-{Key}: {Value}` headers, which take precedence for the same key.
+/// The `OT-Baggage-` prefix is matched ignoring its case.
+fn with_baggage_headers(options: ZipkinContextOptions, carrier: &dyn MapCarrier) -> SpanContext {
+    let context = ZipkinContext::new_partial(options);
+    let context = ImplContextBox::new(context);
+    let mut context = SpanContext::new(context);
+
+    if let Some(header) = find_header(carrier, "baggage") {
+        for (key, value) in baggage::decode(&header) {
+            context.set_baggage_item(key, value);
+        }
+    }
+    for (key, value) in carrier.items() {
+        let prefix = key.get(..11).unwrap_or("");
+        if prefix.eq_ignore_ascii_case("OT-Baggage-") {
+            context.set_baggage_item(String::from(&key[11..]), value.clone());
+        }
+    }
+    context
+}
+
+
+/// Decode context options from the multiple `X-B3-*` headers.
+///
+/// Sampling headers are decoded leniently to support older clients:
+///
+///   * `X-B3-Sampled` accepts `1` or `true` (sampled), `0` or `false` (not sampled)
+///     and `d` (debug).
+///   * `X-B3-Flags` accepts `1` (debug) and `0`.
+///   * Debug contexts are always sampled.
+///
+/// Malformed values are rejected with an error naming the header.
+fn b3_multi_options(carrier: &dyn MapCarrier) -> Result<Option<ZipkinContextOptions>> {
+    // Trace ID: contexts without one only carry the sampling state.
+    let trace_id = match find_header(carrier, "X-B3-TraceId") {
         Some(trace_id) => trace_id,
-        None => return Ok(None)
+        None => return b3_multi_sampling_options(carrier),
     };
-    let trace_id: TraceID = trace_id.parse().map_err(data_encoding_error)?;
+    let trace_id = b3_trace_id("X-B3-TraceId", &trace_id)?;
 
     // Span ID.
-    let span_id = carrier.get("X-B3-SpanId").ok_or(
+    let span_id = find_header(carrier, "X-B3-SpanId").ok_or(
         Error::Msg(String::from("Decoded context does not have a SpanID"))
     )?;
-    let span_id = u64::from_str_radix(&span_id, 16)?;
+    let span_id = b3_span_id("X-B3-SpanId", &span_id)?;
 
     // Build the SpanContext.
     let options = ZipkinContextOptions::default()
-        .debug(carrier.get("X-B3-Flags").unwrap_or(String::from("0")) == "1")
         .span_id(span_id)
         .trace_id(trace_id);
 
     // Parent Span ID.
-    let options = match carrier.get("X-B3-ParentSpanId") {
+    let options = match find_header(carrier, "X-B3-ParentSpanId") {
         None => options,
         Some(parent_span_id) => {
-            let parent_span_id = u64::from_str_radix(&parent_span_id, 16)?;
-            options.parent_span_id(parent_span_id)
+            options.parent_span_id(b3_span_id("X-B3-ParentSpanId", &parent_span_id)?)
         }
     };
-    let options = match carrier.get("X-B3-Sampled") {
-        None => {
-            // TODO: once Samplers are implemented, call it here?
-            options.sampled(true)
+    b3_multi_sampling(carrier, options).map(Some)
+}
+
+/// Decode context options from the `X-B3-Sampled` and `X-B3-Flags` headers only.
+///
+/// Used when the headers carry no identifiers: spans started from the context are
+/// root spans of a new trace that keep the sampling state.
+/// Returns `None` if neither header is present.
+fn b3_multi_sampling_options(carrier: &dyn MapCarrier) -> Result<Option<ZipkinContextOptions>> {
+    let sampled = find_header(carrier, "X-B3-Sampled");
+    let flags = find_header(carrier, "X-B3-Flags");
+    if sampled.is_none() && flags.is_none() {
+        return Ok(None);
+    }
+    b3_multi_sampling(carrier, ZipkinContextOptions::default()).map(Some)
+}
+
+/// Decode the sampling state from the `X-B3-Sampled` and `X-B3-Flags` headers.
+fn b3_multi_sampling(
+    carrier: &dyn MapCarrier, options: ZipkinContextOptions
+) -> Result<ZipkinContextOptions> {
+
+    // Sampling state.
+    let debug = match find_header(carrier, "X-B3-Flags") {
+        None => false,
+        Some(flags) => match flags.trim() {
+            "0" => false,
+            "1" => true,
+            _ => return Err(invalid_b3_value("X-B3-Flags", &flags, "expected 0 or 1")),
         },
-        Some(sampled) => options.sampled(sampled == "1"),
     };
+    let sampling = match find_header(carrier, "X-B3-Sampled") {
+        None => None,
+        Some(sampled) => Some(b3_sampling("X-B3-Sampled", &sampled)?),
+    };
+    let options = match (debug, sampling) {
+        (true, _) | (_, Some(B3Sampling::Debug)) => options.debug(true).sampled(true),
+        (false, Some(B3Sampling::Accept)) => options.sampled(true),
+        (false, Some(B3Sampling::Deny)) => options.sampled(false),
+        (false, None) => options.sampling_state(SamplingState::Defer),
+    };
+    Ok(options)
+}
 
-    let context = ZipkinContext::new_with_options(options);
-    let context = ImplContextBox::new(context);
-    let mut context = SpanContext::new(context);
 
-    for (key, value) in carrier.items() {
-        if key.starts_with("OT-Baggage-") {
-            context.set_baggage_item(String::from(&key[11..]), value.clone());
+/// Decode context options from the single `b3` header.
+///
+/// The header has the format `{TraceId}-{SpanId}-{SamplingState}-{ParentSpanId}`
+/// where the last two fields are optional.
+/// The header can also carry only the sampling state (`b3: 0`, `b3: 1` or `b3: d`),
+/// in which case the context has no identifiers: spans started from it are
+/// root spans of a new trace that keep the sampling state.
+///
+/// The sampling state is decoded as leniently as `X-B3-Sampled`.
+fn b3_single_options(header: &str) -> Result<ZipkinContextOptions> {
+    let fields: Vec<&str> = header.trim().split('-').collect();
+    let sampling = |state: &str, options: ZipkinContextOptions| {
+        let options = match b3_sampling("b3 sampling state", state)? {
+            B3Sampling::Accept => options.sampled(true),
+            B3Sampling::Debug => options.debug(true).sampled(true),
+            B3Sampling::Deny => options.sampled(false),
+        };
+        Ok(options)
+    };
+    if fields.len() == 1 {
+        return sampling(fields[0], ZipkinContextOptions::default());
+    }
+    if fields.len() > 4 {
+        return Err(Error::Msg(format!("Invalid b3 header '{}'", header)));
+    }
+
+    let trace_id = b3_trace_id("b3 trace ID", fields[0])?;
+    let span_id = b3_span_id("b3 span ID", fields[1])?;
+    let options = ZipkinContextOptions::default()
+        .span_id(span_id)
+        .trace_id(trace_id);
+    let options = match fields.get(2) {
+        None => options.sampling_state(SamplingState::Defer),
+        Some(state) => sampling(state, options)?,
+    };
+    let options = match fields.get(3) {
+        None => options,
+        Some(parent_span_id) => {
+            options.parent_span_id(b3_span_id("b3 parent span ID", parent_span_id)?)
         }
+    };
+    Ok(options)
+}
+
+
+/// Sampling states encoded in B3 headers.
+enum B3Sampling {
+    Accept,
+    Debug,
+    Deny,
+}
+
+
+/// Decode a B3 sampling state, accepting both the numeric and boolean forms.
+fn b3_sampling(name: &str, value: &str) -> Result<B3Sampling> {
+    let value = value.trim();
+    if value == "1" || value.eq_ignore_ascii_case("true") {
+        return Ok(B3Sampling::Accept);
     }
-    Ok(Some(context))
+    if value == "0" || value.eq_ignore_ascii_case("false") {
+        return Ok(B3Sampling::Deny);
+    }
+    if value == "d" {
+        return Ok(B3Sampling::Debug);
+    }
+    Err(invalid_b3_value(name, value, "expected 1, true, 0, false or d"))
+}
+
+
+/// Decode a B3 trace ID made of 16 or 32 hex characters.
+fn b3_trace_id(name: &str, value: &str) -> Result<TraceID> {
+    let value = value.trim();
+    if !value.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid_b3_value(name, value, "expected 16 or 32 hex characters"));
+    }
+    value.parse().map_err(|_| invalid_b3_value(name, value, "expected 16 or 32 hex characters"))
+}
+
+
+/// Decode a B3 span ID made of up to 16 hex characters.
+fn b3_span_id(name: &str, value: &str) -> Result<u64> {
+    let value = value.trim();
+    if value.is_empty() || value.len() > 16 || !value.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid_b3_value(name, value, "expected up to 16 hex characters"));
+    }
+    Ok(u64::from_str_radix(value, 16)?)
+}
+
+
+/// Error for malformed B3 values.
+fn invalid_b3_value(name: &str, value: &str, expected: &str) -> Error {
+    Error::Msg(format!("Invalid {} '{}': {}", name, value, expected))
+}
+
+
+/// Checks that the value is made of `min` to `max` hex characters.
+fn is_hex(value: &str, min: usize, max: usize) -> bool {
+    value.len() >= min && value.len() <= max && value.bytes().all(|c| c.is_ascii_hexdigit())
+}
+
+
+/// Checks that the value is made of `len` lowercase hex characters.
+fn is_lower_hex(value: &str, len: usize) -> bool {
+    value.len() == len && value.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+
+/// Decode context options from the W3C `traceparent` and `tracestate` headers.
+///
+/// The `traceparent` header has the format `{version}-{trace-id}-{parent-id}-{flags}`
+/// and is strictly validated: all fields must be lowercase hex of the correct length.
+/// Identifiers that are all zeros are left to the tracer's validation.
+/// Only version `00` is fully supported but, as required by the specification,
+/// the first four fields of future versions are decoded as if they were version `00`.
+///
+/// The `tracestate` header is not interpreted but is propagated as is.
+fn trace_context_options(carrier: &dyn MapCarrier) -> Result<Option<ZipkinContextOptions>> {
+    let header = match find_header(carrier, "traceparent") {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let invalid = || Error::Msg(format!("Invalid traceparent header '{}'", header));
+    let fields: Vec<&str> = header.trim().split('-').collect();
+    if fields.len() < 4 || !is_lower_hex(fields[0], 2) || fields[0] == "ff" {
+        return Err(invalid());
+    }
+    if fields[0] == "00" && fields.len() != 4 {
+        return Err(invalid());
+    }
+    let (trace_id, span_id, flags) = (fields[1], fields[2], fields[3]);
+    if !is_lower_hex(trace_id, 32) || !is_lower_hex(span_id, 16) || !is_lower_hex(flags, 2) {
+        return Err(invalid());
+    }
+    let trace_id: TraceID = trace_id.parse().map_err(data_encoding_error)?;
+    let span_id = u64::from_str_radix(span_id, 16)?;
+    let flags = u8::from_str_radix(flags, 16)?;
+
+    let options = ZipkinContextOptions::default()
+        .sampled(flags & 0x01 == 0x01)
+        .span_id(span_id)
+        .trace_id(trace_id);
+    let options = match find_header(carrier, "tracestate") {
+        Some(ref state) if !state.trim().is_empty() => options.trace_state(state.clone()),
+        _ => options,
+    };
+    Ok(Some(options))
 }
 
 
@@ -134,12 +627,13 @@ mod tests {
     use thrift::protocol::TOutputProtocol;
     use thrift::transport::TBufferedWriteTransport;
 
+    use super::super::context::SamplingState;
     use super::super::context::ZipkinContext;
     use super::super::trace_id::TraceID;
 
     use super::binary;
+    use super::b3_multi_headers;
     use super::binary_format;
-    use super::http_headers;
 
     #[test]
     fn test_binary_decoding() {
@@ -169,7 +663,7 @@ mod tests {
 
         // Decode buffer into a SpanContext.
         let mut cursor = Cursor::new(buffer);
-        let context = binary(Box::new(&mut cursor)).unwrap().unwrap();
+        let context = binary(&mut cursor).unwrap().unwrap();
 
         // Check content.
         let inner = context.impl_context::<ZipkinContext>().unwrap();
@@ -209,7 +703,7 @@ mod tests {
         headers.insert(String::from("OT-Baggage-c"), String::from("3"));
 
         // Check content.
-        let context = http_headers(Box::new(&mut headers)).unwrap().unwrap();
+        let context = b3_multi_headers(&headers).unwrap().unwrap();
         let inner = context.impl_context::<ZipkinContext>().unwrap();
         assert_eq!(
             inner.trace_id(),
@@ -229,4 +723,607 @@ mod tests {
             (String::from("c"), String::from("3"))
         ]);
     }
+
+    #[test]
+    fn test_http_headers_decoding_w3c_baggage() {
+        let mut headers: BTreeMap<String, String> = BTreeMap::new();
+        headers.insert(
+            String::from("X-B3-TraceId"),
+            String::from("0102030405060708090a0b0c0d0e0f10")
+        );
+        headers.insert(String::from("X-B3-SpanId"), String::from("2a"));
+        headers.insert(String::from("baggage"), String::from("a=1,b=two%20words;p=1"));
+        headers.insert(String::from("OT-Baggage-a"), String::from("3"));
+        let context = b3_multi_headers(&headers).unwrap().unwrap();
+
+        let mut items: Vec<(String, String)> = context.baggage_items()
+            .map(|(k, v)| (k.clone(), v.clone())).collect();
+        items.sort();
+        assert_eq!(items, vec![
+            (String::from("a"), String::from("3")),
+            (String::from("b"), String::from("two words")),
+        ]);
+    }
+
+    #[test]
+    fn test_http_headers_decoding_ignores_case() {
+        let mut headers: BTreeMap<String, String> = BTreeMap::new();
+        headers.insert(
+            String::from("x-b3-traceid"),
+            String::from("0102030405060708090a0b0c0d0e0f10")
+        );
+        headers.insert(String::from("x-b3-spanid"), String::from("2a"));
+        headers.insert(String::from("x-b3-parentspanid"), String::from("1"));
+        headers.insert(String::from("x-b3-sampled"), String::from("1"));
+        headers.insert(String::from("ot-baggage-a"), String::from("1"));
+        headers.insert(String::from("OT-BAGGAGE-b"), String::from("2"));
+        let context = b3_multi_headers(&headers).unwrap().unwrap();
+        let inner = context.impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(inner.span_id(), 42);
+        assert_eq!(inner.parent_span_id(), Some(1));
+        assert_eq!(inner.sampling_state(), SamplingState::Accept);
+
+        let mut items: Vec<(String, String)> = context.baggage_items()
+            .map(|(k, v)| (k.clone(), v.clone())).collect();
+        items.sort();
+        assert_eq!(items, vec![
+            (String::from("a"), String::from("1")),
+            (String::from("b"), String::from("2")),
+        ]);
+    }
+
+    #[test]
+    fn test_http_headers_decoding_defers_sampling() {
+        let mut headers: BTreeMap<String, String> = BTreeMap::new();
+        headers.insert(
+            String::from("X-B3-TraceId"),
+            String::from("0102030405060708090a0b0c0d0e0f10")
+        );
+        headers.insert(String::from("X-B3-SpanId"), String::from("2a"));
+        let context = b3_multi_headers(&headers).unwrap().unwrap();
+        let inner = context.impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(inner.sampling_state(), SamplingState::Defer);
+    }
+
+    mod b3_multi_headers {
+        use std::collections::BTreeMap;
+
+        use opentracingrust::Error;
+        use opentracingrust::Result;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::b3_multi_headers;
+
+        fn decode(sampled: Option<&str>, flags: Option<&str>) -> Result<ZipkinContext> {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("X-B3-TraceId"), String::from("090a0b0c0d0e0f10"));
+            headers.insert(String::from("X-B3-SpanId"), String::from("2a"));
+            if let Some(sampled) = sampled {
+                headers.insert(String::from("X-B3-Sampled"), String::from(sampled));
+            }
+            if let Some(flags) = flags {
+                headers.insert(String::from("X-B3-Flags"), String::from(flags));
+            }
+            let context = b3_multi_headers(&headers)?.unwrap();
+            Ok(context.impl_context::<ZipkinContext>().unwrap().clone())
+        }
+
+        fn error(result: Result<ZipkinContext>) -> String {
+            match result {
+                Err(Error::Msg(message)) => message,
+                _ => panic!("Decoding should fail with a message"),
+            }
+        }
+
+        #[test]
+        fn sampled_values() {
+            let accept = ["1", "true", "True"];
+            for sampled in accept.iter() {
+                let context = decode(Some(sampled), None).unwrap();
+                assert_eq!(context.sampling_state(), SamplingState::Accept);
+                assert!(!context.debug());
+            }
+            let deny = ["0", "false", "FALSE"];
+            for sampled in deny.iter() {
+                let context = decode(Some(sampled), None).unwrap();
+                assert_eq!(context.sampling_state(), SamplingState::Deny);
+            }
+        }
+
+        #[test]
+        fn sampled_debug() {
+            let context = decode(Some("d"), None).unwrap();
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            assert!(context.debug());
+        }
+
+        #[test]
+        fn flags_imply_sampled() {
+            let context = decode(None, Some("1")).unwrap();
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            assert!(context.debug());
+            let context = decode(Some("0"), Some("1")).unwrap();
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            let context = decode(None, Some("0")).unwrap();
+            assert_eq!(context.sampling_state(), SamplingState::Defer);
+            assert!(!context.debug());
+        }
+
+        #[test]
+        fn invalid_sampled() {
+            assert_eq!(
+                error(decode(Some("yes"), None)),
+                "Invalid X-B3-Sampled 'yes': expected 1, true, 0, false or d"
+            );
+        }
+
+        #[test]
+        fn invalid_flags() {
+            assert_eq!(error(decode(None, Some("2"))), "Invalid X-B3-Flags '2': expected 0 or 1");
+        }
+
+        #[test]
+        fn invalid_span_id() {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("X-B3-TraceId"), String::from("090a0b0c0d0e0f10"));
+            headers.insert(String::from("X-B3-SpanId"), String::from("+2a"));
+            match b3_multi_headers(&headers) {
+                Err(Error::Msg(message)) => assert_eq!(
+                    message, "Invalid X-B3-SpanId '+2a': expected up to 16 hex characters"
+                ),
+                _ => panic!("Invalid span ID should fail with a message"),
+            };
+        }
+    }
+
+    mod b3_single_header {
+        use std::collections::BTreeMap;
+        use std::str::FromStr;
+
+        use opentracingrust::Error;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::super::trace_id::TraceID;
+        use super::super::b3_single_header;
+
+        fn decode(header: &str) -> ZipkinContext {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("b3"), String::from(header));
+            let context = b3_single_header(&headers).unwrap().unwrap();
+            context.impl_context::<ZipkinContext>().unwrap().clone()
+        }
+
+        #[test]
+        fn full_header() {
+            let context = decode("0102030405060708090a0b0c0d0e0f10-000000000000002a-1-0000000000000001");
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap()
+            );
+            assert_eq!(context.span_id(), 42);
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            assert_eq!(context.parent_span_id(), Some(1));
+            assert!(!context.debug());
+        }
+
+        #[test]
+        fn ids_only() {
+            let context = decode("090a0b0c0d0e0f10-000000000000002a");
+            assert_eq!(context.trace_id(), &TraceID::from_str("090a0b0c0d0e0f10").unwrap());
+            assert_eq!(context.span_id(), 42);
+            assert_eq!(context.sampling_state(), SamplingState::Defer);
+            assert_eq!(context.parent_span_id(), None);
+        }
+
+        #[test]
+        fn debug() {
+            let context = decode("090a0b0c0d0e0f10-000000000000002a-d");
+            assert!(context.debug());
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+        }
+
+        #[test]
+        fn deny_only() {
+            let context = decode("0");
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+            assert!(!context.debug());
+        }
+
+        #[test]
+        fn debug_only() {
+            let context = decode("d");
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            assert!(context.debug());
+            assert_eq!(context.known_span_id(), None);
+            assert_eq!(context.known_trace_id(), None);
+        }
+
+        #[test]
+        fn ignores_case() {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("B3"), String::from("090a0b0c0d0e0f10-000000000000002a"));
+            let context = b3_single_header(&headers).unwrap().unwrap();
+            assert_eq!(context.impl_context::<ZipkinContext>().unwrap().span_id(), 42);
+        }
+
+        #[test]
+        fn boolean_sampling_state() {
+            let context = decode("090a0b0c0d0e0f10-000000000000002a-true");
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            let context = decode("090a0b0c0d0e0f10-000000000000002a-false");
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+        }
+
+        #[test]
+        fn invalid_ids() {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("b3"), String::from("090a0b0c0d0e0f10-zz"));
+            match b3_single_header(&headers) {
+                Err(Error::Msg(message)) => assert_eq!(
+                    message, "Invalid b3 span ID 'zz': expected up to 16 hex characters"
+                ),
+                _ => panic!("Invalid span ID should fail with a message"),
+            };
+        }
+
+        #[test]
+        fn invalid_sampling_state() {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("b3"), String::from("090a0b0c0d0e0f10-000000000000002a-x"));
+            assert!(b3_single_header(&headers).is_err());
+        }
+    }
+
+    mod trace_context {
+        use std::collections::BTreeMap;
+        use std::str::FromStr;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::super::trace_id::TraceID;
+        use super::super::trace_context;
+
+        fn headers(traceparent: &str) -> BTreeMap<String, String> {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("traceparent"), String::from(traceparent));
+            headers
+        }
+
+        fn decode(headers: &BTreeMap<String, String>) -> ZipkinContext {
+            let context = trace_context(headers).unwrap().unwrap();
+            context.impl_context::<ZipkinContext>().unwrap().clone()
+        }
+
+        #[test]
+        fn sampled() {
+            let headers = headers("00-0102030405060708090a0b0c0d0e0f10-000000000000002a-01");
+            let context = decode(&headers);
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap()
+            );
+            assert_eq!(context.span_id(), 42);
+            assert_eq!(context.parent_span_id(), None);
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            assert_eq!(context.trace_state(), None);
+        }
+
+        #[test]
+        fn not_sampled() {
+            let headers = headers("00-0102030405060708090a0b0c0d0e0f10-000000000000002a-00");
+            let context = decode(&headers);
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+        }
+
+        #[test]
+        fn trace_state() {
+            let mut headers = headers("00-0102030405060708090a0b0c0d0e0f10-000000000000002a-01");
+            headers.insert(String::from("tracestate"), String::from("congo=t61rcWkgMzE,rojo=1"));
+            let context = decode(&headers);
+            assert_eq!(context.trace_state(), Some("congo=t61rcWkgMzE,rojo=1"));
+        }
+
+        #[test]
+        fn future_version() {
+            let headers = headers("cc-0102030405060708090a0b0c0d0e0f10-000000000000002a-01-what");
+            let context = decode(&headers);
+            assert_eq!(context.span_id(), 42);
+        }
+
+        #[test]
+        fn invalid_headers() {
+            let invalid = [
+                "00-0102030405060708090a0b0c0d0e0f10-000000000000002a",
+                "00-0102030405060708090a0b0c0d0e0f10-000000000000002a-01-extra",
+                "ff-0102030405060708090a0b0c0d0e0f10-000000000000002a-01",
+                "00-0102030405060708090A0B0C0D0E0F10-000000000000002a-01",
+                "00-0102030405060708-000000000000002a-01",
+                "00-0102030405060708090a0b0c0d0e0f10-000000000000002a-1",
+                "0-0102030405060708090a0b0c0d0e0f10-000000000000002a-01",
+            ];
+            for header in invalid.iter() {
+                let headers = headers(header);
+                assert!(trace_context(&headers).is_err(), "{} is valid", header);
+            }
+        }
+    }
+
+    mod jaeger {
+        use std::collections::BTreeMap;
+        use std::str::FromStr;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::super::trace_id::TraceID;
+        use super::super::jaeger;
+
+        fn headers(header: &str) -> BTreeMap<String, String> {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("uber-trace-id"), String::from(header));
+            headers
+        }
+
+        fn decode(header: &str) -> ZipkinContext {
+            let context = jaeger(&headers(header)).unwrap().unwrap();
+            context.impl_context::<ZipkinContext>().unwrap().clone()
+        }
+
+        #[test]
+        fn full_header() {
+            let context = decode("0102030405060708090a0b0c0d0e0f10:2a:1:1");
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap()
+            );
+            assert_eq!(context.span_id(), 42);
+            assert_eq!(context.parent_span_id(), Some(1));
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+            assert!(!context.debug());
+        }
+
+        #[test]
+        fn short_ids_are_padded() {
+            let context = decode("a0b0c0d0e0f10:2a:0:0");
+            assert_eq!(context.trace_id(), &TraceID::from_str("000a0b0c0d0e0f10").unwrap());
+            assert_eq!(context.parent_span_id(), None);
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+            let context = decode("10203040506070809:2a:0:1");
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::from_str("00000000000000010203040506070809").unwrap()
+            );
+        }
+
+        #[test]
+        fn debug_implies_sampled() {
+            let context = decode("090a0b0c0d0e0f10:2a:0:2");
+            assert!(context.debug());
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+        }
+
+        #[test]
+        fn url_encoded() {
+            let context = decode("090a0b0c0d0e0f10%3A2a%3A0%3A1");
+            assert_eq!(context.span_id(), 42);
+        }
+
+        #[test]
+        fn baggage() {
+            let mut headers = headers("090a0b0c0d0e0f10:2a:0:1");
+            headers.insert(String::from("uberctx-user"), String::from("a%20b"));
+            headers.insert(String::from("UberCtx-team"), String::from("core"));
+            headers.insert(String::from("OT-Baggage-other"), String::from("1"));
+            let context = jaeger(&headers).unwrap().unwrap();
+            let mut items: Vec<(String, String)> = context.baggage_items()
+                .map(|(k, v)| (k.clone(), v.clone())).collect();
+            items.sort();
+            assert_eq!(items, vec![
+                (String::from("team"), String::from("core")),
+                (String::from("user"), String::from("a b")),
+            ]);
+        }
+
+        #[test]
+        fn invalid_headers() {
+            let invalid = [
+                "090a0b0c0d0e0f10:2a:0",
+                "090a0b0c0d0e0f10:2a:0:1:0",
+                "090a0b0c0d0e0f10:zz:0:1",
+                "0102030405060708090a0b0c0d0e0f1011:2a:0:1",
+                "090a0b0c0d0e0f10:2a:0:100",
+            ];
+            for header in invalid.iter() {
+                assert!(jaeger(&headers(header)).is_err(), "{} is valid", header);
+            }
+        }
+    }
+
+    mod xray {
+        use std::collections::BTreeMap;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::super::trace_id::TraceID;
+        use super::super::xray;
+
+        fn headers(header: &str) -> BTreeMap<String, String> {
+            let mut headers: BTreeMap<String, String> = BTreeMap::new();
+            headers.insert(String::from("X-Amzn-Trace-Id"), String::from(header));
+            headers
+        }
+
+        fn decode(header: &str) -> ZipkinContext {
+            let context = xray(&headers(header)).unwrap().unwrap();
+            context.impl_context::<ZipkinContext>().unwrap().clone()
+        }
+
+        #[test]
+        fn full_header() {
+            let context = decode(
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1"
+            );
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::join(0x5759_e988_bd86_2e3f, 0xe1be_46a9_9427_2793)
+            );
+            assert_eq!(context.span_id(), 0x5399_5c3f_42cd_8ad8);
+            assert_eq!(context.parent_span_id(), None);
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+        }
+
+        #[test]
+        fn load_balancer_root() {
+            let context = decode("Root=1-5759e988-bd862e3fe1be46a994272793");
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::join(0x5759_e988_bd86_2e3f, 0xe1be_46a9_9427_2793)
+            );
+            assert_eq!(context.known_span_id(), None);
+            assert_eq!(context.sampling_state(), SamplingState::Defer);
+        }
+
+        #[test]
+        fn sampling_states() {
+            let root = "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8";
+            let context = decode(&format!("{};Sampled=0", root));
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+            let context = decode(&format!("{};Sampled=?", root));
+            assert_eq!(context.sampling_state(), SamplingState::Defer);
+        }
+
+        #[test]
+        fn ignore_other_fields() {
+            let context = decode(
+                "Self=1-67891234-12456789abcdef012345678;Root=1-5759e988-bd862e3fe1be46a994272793;\
+                 CalledFrom=app;Sampled=1"
+            );
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+        }
+
+        #[test]
+        fn invalid_headers() {
+            let invalid = [
+                "Parent=53995c3f42cd8ad8;Sampled=1",
+                "Root=2-5759e988-bd862e3fe1be46a994272793",
+                "Root=1-5759e98-bd862e3fe1be46a994272793",
+                "Root=1-5759e988-bd862e3fe1be46a99427279",
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=2a",
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=yes",
+            ];
+            for header in invalid.iter() {
+                assert!(xray(&headers(header)).is_err(), "{} is valid", header);
+            }
+        }
+    }
+
+    mod grpc_trace_bin {
+        use std::io::Cursor;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::super::trace_id::TraceID;
+        use super::super::grpc_trace_bin;
+
+        const CONTEXT: [u8; 29] = [
+            0,
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+            1, 0, 0, 0, 0, 0, 0, 0, 42,
+            2, 1,
+        ];
+
+        fn decode(buffer: &[u8]) -> ::opentracingrust::Result<Option<ZipkinContext>> {
+            let mut cursor = Cursor::new(buffer);
+            let context = grpc_trace_bin(&mut cursor)?;
+            Ok(context.map(|context| context.impl_context::<ZipkinContext>().unwrap().clone()))
+        }
+
+        #[test]
+        fn full_context() {
+            let context = decode(&CONTEXT).unwrap().unwrap();
+            assert_eq!(
+                context.trace_id(),
+                &TraceID::join(0x0102_0304_0506_0708, 0x090a_0b0c_0d0e_0f10)
+            );
+            assert_eq!(context.span_id(), 42);
+            assert_eq!(context.sampling_state(), SamplingState::Accept);
+        }
+
+        #[test]
+        fn not_sampled() {
+            let mut buffer = CONTEXT;
+            buffer[28] = 0;
+            let context = decode(&buffer).unwrap().unwrap();
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+            let context = decode(&CONTEXT[..27]).unwrap().unwrap();
+            assert_eq!(context.sampling_state(), SamplingState::Deny);
+        }
+
+        #[test]
+        fn ignore_unknown_fields() {
+            let mut buffer = CONTEXT.to_vec();
+            buffer.extend_from_slice(&[3, 1, 2, 3]);
+            let context = decode(&buffer).unwrap().unwrap();
+            assert_eq!(context.span_id(), 42);
+        }
+
+        #[test]
+        fn empty() {
+            assert!(decode(&[]).unwrap().is_none());
+        }
+
+        #[test]
+        fn invalid_contexts() {
+            let mut version = CONTEXT;
+            version[0] = 1;
+            assert!(decode(&version).is_err());
+            assert!(decode(&CONTEXT[..20]).is_err());
+            assert!(decode(&CONTEXT[..28]).is_err());
+            let mut order = CONTEXT;
+            order[18] = 2;
+            assert!(decode(&order).is_err());
+        }
+    }
+
+    mod text_map {
+        use std::collections::BTreeMap;
+
+        use super::super::super::context::SamplingState;
+        use super::super::super::context::ZipkinContext;
+        use super::super::text_map;
+
+        #[test]
+        fn decode_items() {
+            let mut items: BTreeMap<String, String> = BTreeMap::new();
+            items.insert(String::from("x-b3-traceid"), String::from("090a0b0c0d0e0f10"));
+            items.insert(String::from("x-b3-spanid"), String::from("000000000000002a"));
+            items.insert(String::from("x-b3-sampled"), String::from("1"));
+            items.insert(String::from("ot-baggage-%55ser"), String::from("a%20b%2Cc"));
+            let context = text_map(&items).unwrap().unwrap();
+            {
+                let inner = context.impl_context::<ZipkinContext>().unwrap();
+                assert_eq!(inner.span_id(), 42);
+                assert_eq!(inner.sampling_state(), SamplingState::Accept);
+            }
+            let items: Vec<(String, String)> = context.baggage_items()
+                .map(|(k, v)| (k.clone(), v.clone())).collect();
+            assert_eq!(items, vec![(String::from("User"), String::from("a b,c"))]);
+        }
+
+        #[test]
+        fn no_context() {
+            let items: BTreeMap<String, String> = BTreeMap::new();
+            assert!(text_map(&items).unwrap().is_none());
+        }
+
+        #[test]
+        fn invalid_baggage() {
+            let mut items: BTreeMap<String, String> = BTreeMap::new();
+            items.insert(String::from("x-b3-traceid"), String::from("090a0b0c0d0e0f10"));
+            items.insert(String::from("x-b3-spanid"), String::from("000000000000002a"));
+            items.insert(String::from("ot-baggage-a"), String::from("%ff"));
+            assert!(text_map(&items).is_err());
+        }
+    }
 }
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use rand::random;
+
+use super::trace_id::TraceID;
+
+
+/// Generates span and trace IDs for new spans.
+///
+/// Generators are only consulted for IDs of new spans and traces:
+/// extracted contexts keep the IDs they were received with.
+pub trait IdGenerator: Send + Sync {
+    /// Generate the ID of a new span.
+    fn span_id(&self) -> u64;
+
+    /// Generate the ID of a new trace.
+    ///
+    /// Tracers configured to use short trace IDs keep the low 8 bytes of the ID.
+    fn trace_id(&self) -> TraceID;
+}
+
+impl<G: IdGenerator + ?Sized> IdGenerator for Box<G> {
+    fn span_id(&self) -> u64 {
+        (**self).span_id()
+    }
+
+    fn trace_id(&self) -> TraceID {
+        (**self).trace_id()
+    }
+}
+
+impl<G: IdGenerator + ?Sized> IdGenerator for Arc<G> {
+    fn span_id(&self) -> u64 {
+        (**self).span_id()
+    }
+
+    fn trace_id(&self) -> TraceID {
+        (**self).trace_id()
+    }
+}
+
+
+/// An `IdGenerator` that generates random IDs.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn span_id(&self) -> u64 {
+        random::<u64>()
+    }
+
+    fn trace_id(&self) -> TraceID {
+        TraceID::new()
+    }
+}
+
+
+/// An `IdGenerator` that generates the same sequence of IDs for the same seed.
+///
+/// Useful for tests that need predictable IDs: generated IDs are well distributed
+/// but are not random and must not be relied on for security purposes.
+/// IDs are generated with the SplitMix64 algorithm and are never zero.
+#[derive(Debug)]
+pub struct SeededIdGenerator {
+    state: AtomicU64,
+}
+
+impl SeededIdGenerator {
+    /// Returns a generator for the sequence of IDs identified by the seed.
+    pub fn new(seed: u64) -> SeededIdGenerator {
+        SeededIdGenerator {
+            state: AtomicU64::new(seed),
+        }
+    }
+
+    /// Returns the next non-zero number in the sequence.
+    fn next(&self) -> u64 {
+        const GAMMA: u64 = 0x9e37_79b9_7f4a_7c15;
+        loop {
+            let state = self.state.fetch_add(GAMMA, Ordering::Relaxed).wrapping_add(GAMMA);
+            let mut value = state;
+            value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            value ^= value >> 31;
+            if value != 0 {
+                return value;
+            }
+        }
+    }
+}
+
+impl IdGenerator for SeededIdGenerator {
+    fn span_id(&self) -> u64 {
+        self.next()
+    }
+
+    fn trace_id(&self) -> TraceID {
+        TraceID::join(self.next(), self.next())
+    }
+}
+
+
+/// An `IdGenerator` that prefixes 16 bytes trace IDs with the current time.
+///
+/// The first 4 bytes of trace IDs are the seconds since the UNIX epoch
+/// and the remaining 12 bytes are random, so trace IDs are roughly time-ordered
+/// and compatible with AWS X-Ray.
+/// See https://docs.aws.amazon.com/xray/latest/devguide/xray-api-sendingdata.html#xray-api-traceids
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TimeIdGenerator;
+
+impl IdGenerator for TimeIdGenerator {
+    fn span_id(&self) -> u64 {
+        random::<u64>()
+    }
+
+    fn trace_id(&self) -> TraceID {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as u32)
+            .unwrap_or(0);
+        let mut id = random::<[u8; 16]>();
+        id[0..4].copy_from_slice(&seconds.to_be_bytes());
+        TraceID::from(id)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+    use std::time::UNIX_EPOCH;
+
+    use super::IdGenerator;
+    use super::RandomIdGenerator;
+    use super::SeededIdGenerator;
+    use super::TimeIdGenerator;
+
+    #[test]
+    fn random_ids() {
+        let generator = RandomIdGenerator;
+        assert_ne!(generator.span_id(), generator.span_id());
+        assert_ne!(generator.trace_id(), generator.trace_id());
+    }
+
+    #[test]
+    fn seeded_ids_are_deterministic() {
+        let first = SeededIdGenerator::new(42);
+        let second = SeededIdGenerator::new(42);
+        assert_eq!(first.trace_id(), second.trace_id());
+        assert_eq!(first.span_id(), second.span_id());
+        assert_ne!(first.span_id(), SeededIdGenerator::new(43).span_id());
+    }
+
+    #[test]
+    fn seeded_ids_are_not_repeated() {
+        let generator = SeededIdGenerator::new(0);
+        let first = generator.span_id();
+        assert_ne!(first, 0);
+        assert_ne!(first, generator.span_id());
+    }
+
+    #[test]
+    fn time_prefixed_trace_ids() {
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let (high, _) = TimeIdGenerator.trace_id().split();
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let seconds = high >> 32;
+        assert!(before <= seconds && seconds <= after);
+    }
+}
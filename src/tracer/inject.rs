@@ -1,82 +1,379 @@
 use std::collections::BTreeMap;
 use std::io::Write;
 
+use byteorder::NetworkEndian;
+use byteorder::WriteBytesExt;
+
+use opentracingrust::Error;
 use opentracingrust::MapCarrier;
 use opentracingrust::Result;
 use opentracingrust::SpanContext;
 
 use thrift::protocol::TBinaryOutputProtocol;
+use thrift::protocol::TCompactOutputProtocol;
 use thrift::protocol::TOutputProtocol;
 use thrift::transport::TBufferedWriteTransport;
 
+use super::baggage;
+use super::context::SamplingState;
 use super::context::ZipkinContext;
 use super::error::thrift_error;
+use super::propagator::ThriftProtocol;
+use super::propagator::BINARY_MAGIC;
+use super::propagator::BINARY_VERSION;
+use super::trace_id::TraceID;
 use super::super::thrift_gen::binary_format;
 
 
 /// Encode the SpanContext into a thrift structure.
-pub fn binary(context: &SpanContext, carrier: Box<&mut dyn Write>) -> Result<()> {
+///
+/// Contexts without trace and span IDs can't be encoded and are rejected.
+pub fn binary(context: &SpanContext, carrier: &mut dyn Write) -> Result<()> {
+    let thrift_context = to_thrift(context)?;
+    let transport = TBufferedWriteTransport::new(carrier);
+    let mut protocol = TBinaryOutputProtocol::new(transport, true);
+    thrift_context.write_to_out_protocol(&mut protocol).map_err(thrift_error)?;
+    protocol.flush().map_err(thrift_error)
+}
+
+
+/// Encode the SpanContext into a thrift structure preceded by a version header.
+///
+/// The header is made of four bytes: the `ZK` magic bytes, the format version
+/// (currently `1`) and the thrift protocol the structure is encoded with
+/// (`0` for binary, `1` for compact).
+///
+/// Contexts without trace and span IDs can't be encoded and are rejected.
+pub fn versioned_binary(
+    context: &SpanContext, carrier: &mut dyn Write, protocol: ThriftProtocol
+) -> Result<()> {
+    let thrift_context = to_thrift(context)?;
+    carrier.write_all(&BINARY_MAGIC)?;
+    carrier.write_all(&[BINARY_VERSION, protocol.id()])?;
+    match protocol {
+        ThriftProtocol::Binary => binary(context, carrier),
+        ThriftProtocol::Compact => {
+            let transport = TBufferedWriteTransport::new(carrier);
+            let mut protocol = TCompactOutputProtocol::new(transport);
+            thrift_context.write_to_out_protocol(&mut protocol).map_err(thrift_error)?;
+            protocol.flush().map_err(thrift_error)
+        }
+    }
+}
+
+
+/// Build the thrift structure for the SpanContext.
+fn to_thrift(context: &SpanContext) -> Result<binary_format::SpanContext> {
     let inner_context = context.impl_context::<ZipkinContext>().expect(
         "Invalid SpanContext, was it created by ZipkinTracer?"
     );
-    let (high, low) = inner_context.trace_id().split();
-    let span_id = inner_context.span_id();
+    let (trace_id, span_id) = required_ids(inner_context, "thrift")?;
+    let (high, low) = trace_id.split();
     let parent_span_id = inner_context.parent_span_id().map(|id| id as i64);
-    let sampled = inner_context.sampled();
+    let sampled = match inner_context.sampling_state() {
+        SamplingState::Accept => Some(true),
+        SamplingState::Defer => None,
+        SamplingState::Deny => Some(false),
+    };
     let flags = match inner_context.debug() {
         false => 0,
         true => 1,
     };
     let items: BTreeMap<String, String> = context.baggage_items()
         .map(|(k, v)| (k.clone(), v.clone())).collect();
-    let thrift_context = binary_format::SpanContext::new(
+    Ok(binary_format::SpanContext::new(
         Some(high as i64),     // Trace ID
         Some(low as i64),      // Trace ID (High)
         Some(span_id as i64),  // Span ID
         parent_span_id,        // Parent Span ID
-        Some(sampled),         // Sampled?
+        sampled,               // Sampled?
         Some(flags),           // Flags
         Some(items)            // Baggage Items
+    ))
+}
+
+
+/// Encode the SpanContext into the gRPC `grpc-trace-bin` binary format.
+///
+/// The format is the OpenCensus binary layout: a version byte (`0`) followed by
+/// the trace ID (field `0`, 16 bytes), the span ID (field `1`, 8 bytes) and
+/// the trace options (field `2`, 1 byte) with bit `1` set for sampled contexts.
+/// Short trace IDs are left-padded with zeros and debug contexts are encoded as sampled.
+///
+/// The format has no deferred sampling state, parent span ID or baggage items:
+/// deferred contexts are encoded as not sampled and the rest is not propagated.
+/// Contexts without trace and span IDs can't be encoded and are rejected.
+/// See https://github.com/census-instrumentation/opencensus-specs/blob/master/encodings/BinaryEncoding.md
+pub fn grpc_trace_bin(context: &SpanContext, carrier: &mut dyn Write) -> Result<()> {
+    let inner_context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
     );
-    let transport = TBufferedWriteTransport::new(carrier);
-    let mut protocol = TBinaryOutputProtocol::new(transport, true);
-    thrift_context.write_to_out_protocol(&mut protocol).map_err(thrift_error)?;
-    protocol.flush().map_err(thrift_error)
+    let (trace_id, span_id) = required_ids(inner_context, "grpc-trace-bin")?;
+    let (high, low) = trace_id.split();
+    let options = match inner_context.debug() || inner_context.sampled() {
+        false => 0,
+        true => 1,
+    };
+    let mut buffer = Vec::with_capacity(29);
+    buffer.push(0);
+    buffer.push(0);
+    buffer.write_u64::<NetworkEndian>(high)?;
+    buffer.write_u64::<NetworkEndian>(low)?;
+    buffer.push(1);
+    buffer.write_u64::<NetworkEndian>(span_id)?;
+    buffer.push(2);
+    buffer.push(options);
+    carrier.write_all(&buffer)?;
+    Ok(())
 }
 
 
-/// Encode the SpanContext into HTTP Headers.
+/// Encode the SpanContext into the multiple `X-B3-*` HTTP Headers.
 ///
 /// The encoding is done following the B3 propagation format.
 /// See https://github.com/openzipkin/b3-propagation
 ///
-/// Baggage items are added to the headers with `OT-Baggage-{Key}: {Value}`.
-pub fn http_headers(context: &SpanContext, carrier: Box<&mut dyn MapCarrier>) -> Result<()> {
+/// The `X-B3-Sampled` header is omitted if the sampling decision is deferred.
+/// Contexts without trace and span IDs are encoded with the sampling headers only.
+///
+/// Baggage items are encoded as described in `baggage_headers`.
+pub fn b3_multi_headers(context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
     let inner_context = context.impl_context::<ZipkinContext>().expect(
         "Invalid SpanContext, was it created by ZipkinTracer?"
     );
-    if let Some(parent_span_id) = inner_context.parent_span_id() {
-        let parent_span_id = format!("{:x}", parent_span_id);
-        carrier.set("X-B3-ParentSpanId", &parent_span_id);
+    if let Some((trace_id, span_id)) = known_ids(inner_context) {
+        if let Some(parent_span_id) = inner_context.parent_span_id() {
+            let parent_span_id = format!("{:x}", parent_span_id);
+            carrier.set("X-B3-ParentSpanId", &parent_span_id);
+        }
+        let span_id = format!("{:x}", span_id);
+        carrier.set("X-B3-SpanId", &span_id);
+        carrier.set("X-B3-TraceId", &trace_id.to_string());
     }
-    let span_id = format!("{:x}", inner_context.span_id());
-    carrier.set("X-B3-SpanId", &span_id);
-
-    carrier.set("X-B3-TraceId", &inner_context.trace_id().to_string());
     carrier.set("X-B3-Flags", match inner_context.debug() {
         false => "0",
         true  => "1",
     });
-    carrier.set("X-B3-Sampled", match inner_context.sampled() {
+    match inner_context.sampling_state() {
+        SamplingState::Accept => carrier.set("X-B3-Sampled", "1"),
+        SamplingState::Defer => (),
+        SamplingState::Deny => carrier.set("X-B3-Sampled", "0"),
+    };
+    baggage_headers(context, carrier);
+    Ok(())
+}
+
+
+/// Encode the SpanContext into the single `b3` HTTP Header.
+///
+/// The header has the format `{TraceId}-{SpanId}-{SamplingState}-{ParentSpanId}`.
+/// The sampling state is `d` for debug contexts and is omitted, along with
+/// the parent span id, if the sampling decision is deferred.
+/// Contexts without trace and span IDs are encoded with the sampling state only
+/// (`b3: {SamplingState}`) and the header is omitted if the decision is also deferred.
+/// See https://github.com/openzipkin/b3-propagation
+///
+/// Baggage items are encoded as described in `baggage_headers`.
+pub fn b3_single_header(context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+    let inner_context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    let sampling = match (inner_context.debug(), inner_context.sampling_state()) {
+        (true, _) => Some("d"),
+        (false, SamplingState::Accept) => Some("1"),
+        (false, SamplingState::Defer) => None,
+        (false, SamplingState::Deny) => Some("0"),
+    };
+    let header = match (known_ids(inner_context), sampling) {
+        (Some((trace_id, span_id)), sampling) => {
+            let mut header = format!("{}-{:016x}", trace_id, span_id);
+            if let Some(sampling) = sampling {
+                header.push('-');
+                header.push_str(sampling);
+                if let Some(parent_span_id) = inner_context.parent_span_id() {
+                    header.push_str(&format!("-{:016x}", parent_span_id));
+                }
+            }
+            Some(header)
+        }
+        (None, sampling) => sampling.map(String::from),
+    };
+    if let Some(header) = header {
+        carrier.set("b3", &header);
+    }
+    baggage_headers(context, carrier);
+    Ok(())
+}
+
+
+/// Encode the SpanContext into the W3C `traceparent` and `tracestate` HTTP Headers.
+///
+/// Version `00` of the format is used: `00-{trace-id}-{parent-id}-{flags}`.
+/// Short trace IDs are left-padded with zeros and the sampled flag is set
+/// for sampled and debug contexts.
+/// The `tracestate` header is added if the context carries one.
+/// Contexts without trace and span IDs can't be encoded and are rejected.
+/// See https://www.w3.org/TR/trace-context/
+///
+/// Baggage items are encoded as described in `baggage_headers`.
+pub fn trace_context(context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+    let inner_context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    let (trace_id, span_id) = required_ids(inner_context, "traceparent")?;
+    let (high, low) = trace_id.split();
+    let flags = match inner_context.debug() || inner_context.sampled() {
+        false => "00",
+        true => "01",
+    };
+    let header = format!("00-{:016x}{:016x}-{:016x}-{}", high, low, span_id, flags);
+    carrier.set("traceparent", &header);
+    if let Some(trace_state) = inner_context.trace_state() {
+        carrier.set("tracestate", trace_state);
+    }
+    baggage_headers(context, carrier);
+    Ok(())
+}
+
+
+/// Encode the SpanContext into the Jaeger `uber-trace-id` HTTP Header.
+///
+/// The header has the format `{trace-id}:{span-id}:{parent-span-id}:{flags}`
+/// where the parent span ID is `0` for root spans.
+/// Flags have bit `1` set for sampled contexts and bit `2` for debug contexts.
+/// Jaeger does not support deferred sampling decisions so they are encoded as not sampled.
+/// Contexts without trace and span IDs can't be encoded and are rejected.
+/// See https://www.jaegertracing.io/docs/latest/client-libraries/#propagation-format
+///
+/// Baggage items are added to the headers with `uberctx-{Key}: {Value}`,
+/// with values percent-encoded.
+pub fn jaeger(context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+    let inner_context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    let (trace_id, span_id) = required_ids(inner_context, "uber-trace-id")?;
+    let mut flags = 0;
+    if inner_context.sampled() {
+        flags |= 0x01;
+    }
+    if inner_context.debug() {
+        flags |= 0x01 | 0x02;
+    }
+    let header = format!(
+        "{}:{:016x}:{:x}:{:x}", trace_id, span_id,
+        inner_context.parent_span_id().unwrap_or(0), flags
+    );
+    carrier.set("uber-trace-id", &header);
+    for (key, value) in context.baggage_items() {
+        let key = format!("uberctx-{}", key);
+        carrier.set(&key, &baggage::encode_value(value));
+    }
+    Ok(())
+}
+
+
+/// Encode the SpanContext into the AWS X-Ray `X-Amzn-Trace-Id` HTTP Header.
+///
+/// The header has the format `Root=1-{epoch}-{unique-id};Parent={span-id};Sampled={0|1}`
+/// where the epoch is the first 8 hex characters of the 128-bit trace ID and the
+/// unique ID is the other 24. Short trace IDs are left-padded with zeros.
+/// The `Sampled` field is omitted if the sampling decision is deferred and the `Parent`
+/// field is omitted for contexts without a span ID, such as those received from load balancers.
+/// Contexts without a trace ID can't be encoded and are rejected.
+/// See https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader
+///
+/// Baggage items are encoded as described in `baggage_headers`.
+pub fn xray(context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+    let inner_context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    let trace_id = inner_context.known_trace_id().ok_or_else(|| Error::Msg(String::from(
+        "Context without a trace ID can't be encoded as X-Amzn-Trace-Id"
+    )))?;
+    let (high, low) = trace_id.split();
+    let mut header = format!("Root=1-{:08x}-{:08x}{:016x}", high >> 32, high & 0xffff_ffff, low);
+    if let Some(span_id) = inner_context.known_span_id() {
+        header.push_str(&format!(";Parent={:016x}", span_id));
+    }
+    match (inner_context.debug(), inner_context.sampling_state()) {
+        (true, _) | (false, SamplingState::Accept) => header.push_str(";Sampled=1"),
+        (false, SamplingState::Defer) => (),
+        (false, SamplingState::Deny) => header.push_str(";Sampled=0"),
+    };
+    carrier.set("X-Amzn-Trace-Id", &header);
+    baggage_headers(context, carrier);
+    Ok(())
+}
+
+
+/// Encode the SpanContext into a text map safe for non-HTTP carriers.
+///
+/// The context is encoded with lowercase B3 keys (`x-b3-traceid`, `x-b3-spanid`,
+/// `x-b3-parentspanid`, `x-b3-flags` and `x-b3-sampled`), with the `x-b3-sampled`
+/// key omitted if the sampling decision is deferred.
+/// Contexts without trace and span IDs are encoded with the sampling keys only.
+///
+/// Baggage items are added with `ot-baggage-{key}: {value}` items where keys and
+/// values are percent-encoded so that all keys are lowercase and any item round-trips.
+pub fn text_map(context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+    let inner_context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    if let Some((trace_id, span_id)) = known_ids(inner_context) {
+        carrier.set("x-b3-traceid", &trace_id.to_string());
+        carrier.set("x-b3-spanid", &format!("{:016x}", span_id));
+        if let Some(parent_span_id) = inner_context.parent_span_id() {
+            carrier.set("x-b3-parentspanid", &format!("{:016x}", parent_span_id));
+        }
+    }
+    carrier.set("x-b3-flags", match inner_context.debug() {
         false => "0",
-        true  => "1",
+        true => "1",
     });
+    match inner_context.sampling_state() {
+        SamplingState::Accept => carrier.set("x-b3-sampled", "1"),
+        SamplingState::Defer => (),
+        SamplingState::Deny => carrier.set("x-b3-sampled", "0"),
+    };
+    for (key, value) in context.baggage_items() {
+        let key = format!("ot-baggage-{}", baggage::encode_text_map_key(key));
+        carrier.set(&key, &baggage::encode_text_map_value(value));
+    }
+    Ok(())
+}
+
+
+/// Access the trace and span IDs of the context, if it has both.
+///
+/// Extracted contexts can carry only sampling flags or a trace ID.
+fn known_ids(context: &ZipkinContext) -> Option<(&TraceID, u64)> {
+    match (context.known_trace_id(), context.known_span_id()) {
+        (Some(trace_id), Some(span_id)) => Some((trace_id, span_id)),
+        _ => None,
+    }
+}
 
+/// Access the trace and span IDs of the context for formats that require them.
+fn required_ids<'a>(context: &'a ZipkinContext, format: &str) -> Result<(&'a TraceID, u64)> {
+    known_ids(context).ok_or_else(|| Error::Msg(format!(
+        "Context without trace and span IDs can't be encoded as {}", format
+    )))
+}
+
+
+/// Encode baggage items into HTTP Headers.
+///
+/// Items are encoded both as `OT-Baggage-{Key}: {Value}` headers and
+/// in the W3C `baggage` header for interoperability with OpenTelemetry.
+/// Items with keys that are not HTTP tokens are only encoded as `OT-Baggage-*` headers.
+fn baggage_headers(context: &SpanContext, carrier: &mut dyn MapCarrier) {
     for (key, value) in context.baggage_items() {
         let key = format!("OT-Baggage-{}", key);
         carrier.set(&key, value);
     }
-    Ok(())
+    if let Some(header) = baggage::encode(context.baggage_items()) {
+        carrier.set("baggage", &header);
+    }
 }
 
 
@@ -93,13 +390,20 @@ mod tests {
     use thrift::protocol::TBinaryInputProtocol;
     use thrift::transport::TBufferedReadTransport;
 
+    use super::super::context::SamplingState;
     use super::super::context::ZipkinContext;
     use super::super::context::ZipkinContextOptions;
     use super::super::trace_id::TraceID;
 
+    use super::b3_multi_headers;
+    use super::b3_single_header;
+    use super::grpc_trace_bin;
+    use super::jaeger;
+    use super::text_map;
     use super::binary;
     use super::binary_format;
-    use super::http_headers;
+    use super::trace_context;
+    use super::xray;
 
     fn make_context() -> SpanContext {
         let options = ZipkinContextOptions::default()
@@ -122,7 +426,7 @@ mod tests {
         let mut buffer: Vec<u8> = Vec::new();
         let mut context = make_context();
         context.reference_span(&SpanReference::ChildOf(make_context()));
-        binary(&context, Box::new(&mut buffer)).unwrap();
+        binary(&context, &mut buffer).unwrap();
         assert_ne!(buffer.len(), 0);
 
         // Decode the context from memory.
@@ -155,7 +459,7 @@ mod tests {
         let mut headers: HashMap<String, String> = HashMap::new();
         let mut context = make_context();
         context.reference_span(&SpanReference::ChildOf(make_context()));
-        http_headers(&context, Box::new(&mut headers)).unwrap();
+        b3_multi_headers(&context, &mut headers).unwrap();
 
         // Validate content.
         assert_eq!(headers.get("X-B3-TraceId").unwrap(), "0102030405060708090a0b0c0d0e0f10");
@@ -166,5 +470,282 @@ mod tests {
         assert_eq!(headers.get("OT-Baggage-a").unwrap(), "1");
         assert_eq!(headers.get("OT-Baggage-b").unwrap(), "2");
         assert_eq!(headers.get("OT-Baggage-c").unwrap(), "3");
+        assert_eq!(headers.get("baggage").unwrap(), "a=1,b=2,c=3");
+    }
+
+    #[test]
+    fn test_headers_encoding_deferred_sampling() {
+        let options = ZipkinContextOptions::default()
+            .sampling_state(SamplingState::Defer)
+            .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        let mut headers: HashMap<String, String> = HashMap::new();
+        b3_multi_headers(&context, &mut headers).unwrap();
+        assert_eq!(headers.get("X-B3-TraceId").unwrap(), "0102030405060708090a0b0c0d0e0f10");
+        assert_eq!(headers.get("X-B3-Sampled"), None);
+    }
+
+    #[test]
+    fn test_b3_single_header_encoding() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampled(true)
+            .span_id(42)
+            .parent_span_id(1)
+            .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let mut context = SpanContext::new(ImplContextBox::new(context));
+        context.set_baggage_item(String::from("a"), String::from("1"));
+        b3_single_header(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("b3").unwrap(),
+            "0102030405060708090a0b0c0d0e0f10-000000000000002a-1-0000000000000001"
+        );
+        assert_eq!(headers.get("OT-Baggage-a").unwrap(), "1");
+        assert_eq!(headers.get("X-B3-TraceId"), None);
+    }
+
+    #[test]
+    fn test_b3_single_header_encoding_debug() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let mut context = make_context();
+        context.reference_span(&SpanReference::ChildOf(make_context()));
+        b3_single_header(&context, &mut headers).unwrap();
+        let header = headers.get("b3").unwrap();
+        assert!(header.starts_with("0102030405060708090a0b0c0d0e0f10-"));
+        assert!(header.ends_with("-d-000000000000002a"));
+    }
+
+    #[test]
+    fn test_b3_single_header_encoding_deferred() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampling_state(SamplingState::Defer)
+            .span_id(42)
+            .parent_span_id(1)
+            .trace_id(TraceID::from_str("090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        b3_single_header(&context, &mut headers).unwrap();
+        assert_eq!(headers.get("b3").unwrap(), "090a0b0c0d0e0f10-000000000000002a");
+    }
+
+    #[test]
+    fn test_trace_context_encoding() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampled(true)
+            .span_id(42)
+            .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap())
+            .trace_state(String::from("rojo=00f067aa0ba902b7"));
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        trace_context(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("traceparent").unwrap(),
+            "00-0102030405060708090a0b0c0d0e0f10-000000000000002a-01"
+        );
+        assert_eq!(headers.get("tracestate").unwrap(), "rojo=00f067aa0ba902b7");
+    }
+
+    #[test]
+    fn test_trace_context_encoding_short_id() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampled(false)
+            .span_id(42)
+            .trace_id(TraceID::from_str("090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        trace_context(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("traceparent").unwrap(),
+            "00-0000000000000000090a0b0c0d0e0f10-000000000000002a-00"
+        );
+        assert_eq!(headers.get("tracestate"), None);
+    }
+
+    #[test]
+    fn test_jaeger_encoding() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .parent_span_id(1)
+            .sampled(true)
+            .span_id(42)
+            .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let mut context = SpanContext::new(ImplContextBox::new(context));
+        context.set_baggage_item(String::from("user"), String::from("a b"));
+        jaeger(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("uber-trace-id").unwrap(),
+            "0102030405060708090a0b0c0d0e0f10:000000000000002a:1:1"
+        );
+        assert_eq!(headers.get("uberctx-user").unwrap(), "a%20b");
+        assert_eq!(headers.get("OT-Baggage-user"), None);
+    }
+
+    #[test]
+    fn test_jaeger_encoding_root_debug() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .debug(true)
+            .sampled(false)
+            .span_id(42)
+            .trace_id(TraceID::from_str("090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        jaeger(&context, &mut headers).unwrap();
+        assert_eq!(headers.get("uber-trace-id").unwrap(), "090a0b0c0d0e0f10:000000000000002a:0:3");
+    }
+
+    #[test]
+    fn test_xray_encoding() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampled(true)
+            .span_id(0x5399_5c3f_42cd_8ad8)
+            .trace_id(TraceID::from_str("5759e988bd862e3fe1be46a994272793").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        xray(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("X-Amzn-Trace-Id").unwrap(),
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1"
+        );
+    }
+
+    #[test]
+    fn test_xray_encoding_deferred_short_id() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampling_state(SamplingState::Defer)
+            .span_id(42)
+            .trace_id(TraceID::from_str("090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        xray(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("X-Amzn-Trace-Id").unwrap(),
+            "Root=1-00000000-00000000090a0b0c0d0e0f10;Parent=000000000000002a"
+        );
+    }
+
+    #[test]
+    fn test_xray_encoding_without_parent() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .sampled(false)
+            .trace_id(TraceID::from_str("5759e988bd862e3fe1be46a994272793").unwrap());
+        let context = ZipkinContext::new_partial(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        xray(&context, &mut headers).unwrap();
+        assert_eq!(
+            headers.get("X-Amzn-Trace-Id").unwrap(),
+            "Root=1-5759e988-bd862e3fe1be46a994272793;Sampled=0"
+        );
+    }
+
+    #[test]
+    fn test_grpc_trace_bin_encoding() {
+        let options = ZipkinContextOptions::default()
+            .sampled(true)
+            .span_id(42)
+            .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        let mut buffer: Vec<u8> = Vec::new();
+        grpc_trace_bin(&context, &mut buffer).unwrap();
+        assert_eq!(buffer, vec![
+            0,
+            0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+            1, 0, 0, 0, 0, 0, 0, 0, 42,
+            2, 1,
+        ]);
+    }
+
+    #[test]
+    fn test_grpc_trace_bin_encoding_short_id() {
+        let options = ZipkinContextOptions::default()
+            .sampling_state(SamplingState::Defer)
+            .span_id(42)
+            .trace_id(TraceID::from_str("090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let context = SpanContext::new(ImplContextBox::new(context));
+        let mut buffer: Vec<u8> = Vec::new();
+        grpc_trace_bin(&context, &mut buffer).unwrap();
+        assert_eq!(buffer, vec![
+            0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 9, 10, 11, 12, 13, 14, 15, 16,
+            1, 0, 0, 0, 0, 0, 0, 0, 42,
+            2, 0,
+        ]);
+    }
+
+    #[test]
+    fn test_text_map_encoding() {
+        let mut items: HashMap<String, String> = HashMap::new();
+        let options = ZipkinContextOptions::default()
+            .parent_span_id(1)
+            .sampled(true)
+            .span_id(42)
+            .trace_id(TraceID::from_str("0102030405060708090a0b0c0d0e0f10").unwrap());
+        let context = ZipkinContext::new_with_options(options);
+        let mut context = SpanContext::new(ImplContextBox::new(context));
+        context.set_baggage_item(String::from("User"), String::from("a b"));
+        text_map(&context, &mut items).unwrap();
+        assert_eq!(items.get("x-b3-traceid").unwrap(), "0102030405060708090a0b0c0d0e0f10");
+        assert_eq!(items.get("x-b3-spanid").unwrap(), "000000000000002a");
+        assert_eq!(items.get("x-b3-parentspanid").unwrap(), "0000000000000001");
+        assert_eq!(items.get("x-b3-flags").unwrap(), "0");
+        assert_eq!(items.get("x-b3-sampled").unwrap(), "1");
+        assert_eq!(items.get("ot-baggage-%55ser").unwrap(), "a%20b");
+        assert!(items.keys().all(|key| key.to_lowercase() == *key));
+    }
+
+    fn sampling_only_context(sampling: SamplingState) -> SpanContext {
+        let options = ZipkinContextOptions::default().sampling_state(sampling);
+        SpanContext::new(ImplContextBox::new(ZipkinContext::new_partial(options)))
+    }
+
+    #[test]
+    fn test_sampling_only_encoding() {
+        let context = sampling_only_context(SamplingState::Deny);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        b3_multi_headers(&context, &mut headers).unwrap();
+        b3_single_header(&context, &mut headers).unwrap();
+        assert_eq!(headers.get("b3").unwrap(), "0");
+        assert_eq!(headers.get("X-B3-Sampled").unwrap(), "0");
+        assert_eq!(headers.get("X-B3-Flags").unwrap(), "0");
+        assert!(!headers.contains_key("X-B3-TraceId"));
+        assert!(!headers.contains_key("X-B3-SpanId"));
+
+        let mut items: HashMap<String, String> = HashMap::new();
+        text_map(&context, &mut items).unwrap();
+        assert_eq!(items.get("x-b3-sampled").unwrap(), "0");
+        assert!(!items.contains_key("x-b3-traceid"));
+    }
+
+    #[test]
+    fn test_deferred_sampling_only_encoding() {
+        let context = sampling_only_context(SamplingState::Defer);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        b3_single_header(&context, &mut headers).unwrap();
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn test_contexts_without_ids_are_rejected() {
+        let context = sampling_only_context(SamplingState::Accept);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        assert!(trace_context(&context, &mut headers).is_err());
+        assert!(jaeger(&context, &mut headers).is_err());
+        assert!(xray(&context, &mut headers).is_err());
+        assert!(headers.is_empty());
+        let mut buffer: Vec<u8> = Vec::new();
+        assert!(binary(&context, &mut buffer).is_err());
+        assert!(grpc_trace_bin(&context, &mut buffer).is_err());
+        assert!(buffer.is_empty());
     }
 }
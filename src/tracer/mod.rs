@@ -12,14 +12,45 @@ use opentracingrust::StartOptions;
 use opentracingrust::Tracer;
 use opentracingrust::TracerInterface;
 
+use super::sampler::ConstSampler;
+use super::sampler::Sampler;
+use super::sampler::SamplingDecision;
+use super::sampler::SamplingRequest;
+
+mod baggage;
 mod context;
 mod error;
 mod extract;
+mod id_generator;
 mod inject;
+mod propagator;
 mod trace_id;
+mod validation;
 
+pub use self::context::SamplingState;
 pub use self::context::ZipkinContext;
 pub use self::context::ZipkinContextOptions;
+pub use self::id_generator::IdGenerator;
+pub use self::id_generator::RandomIdGenerator;
+pub use self::id_generator::SeededIdGenerator;
+pub use self::id_generator::TimeIdGenerator;
+pub use self::propagator::B3MultiPropagator;
+pub use self::propagator::B3SinglePropagator;
+pub use self::propagator::BinaryPropagator;
+pub use self::propagator::CompositePropagator;
+pub use self::propagator::GrpcTraceBinPropagator;
+pub use self::propagator::JaegerPropagator;
+pub use self::propagator::Propagator;
+pub use self::propagator::TextMapPropagator;
+pub use self::propagator::ThriftBinaryPropagator;
+pub use self::propagator::ThriftProtocol;
+pub use self::propagator::TraceContextPropagator;
+pub use self::propagator::XRayPropagator;
+pub use self::trace_id::TraceID;
+pub use self::trace_id::TraceIdMode;
+pub use self::validation::InvalidContext;
+pub use self::validation::InvalidContextCounter;
+pub use self::validation::InvalidContextPolicy;
 
 /// A Zipkin backed OpenTracingRust tracer.
 ///
@@ -28,40 +59,586 @@ pub use self::context::ZipkinContextOptions;
 /// Sampling policy:
 ///
 ///   * Any span inherits the sampling state from its references.
-///   * Root spans are always sampled: currently sampling policies are not supported.
+///   * Root spans are sampled according to the configured `Sampler` (all by default).
+///   * Spans referencing a context that deferred the decision (for example because an
+///     upstream service omitted the `X-B3-Sampled` header) are also sampled by the `Sampler`.
+///   * Debug spans that need a decision are always sampled.
+///
+/// The decision for a deferred context is not recorded on the context itself:
+/// each span started from it asks the `Sampler` again.
+/// Only samplers keyed on the trace ID, such as `ProbabilisticSampler`, make the same
+/// decision for all of them; others, like `RateLimitingSampler` and `RuleSampler`,
+/// can sample some of the spans of a trace.
+/// Start a single span from deferred contexts, and children from that span, to avoid this.
+///
+/// Extracted contexts are validated according to the B3 specification: see
+/// `ZipkinTracerOptions::invalid_context_policy` for how invalid contexts are handled.
+///
+/// Spans that are started without references and only later marked as a child
+/// (with `Span::child_of` or `Span::follows`) still consult the sampler but the
+/// decision is replaced by the state of the reference.
 pub struct ZipkinTracer {
+    binary_propagator: Box<dyn BinaryPropagator>,
+    id_generator: Box<dyn IdGenerator>,
+    invalid_context_counter: InvalidContextCounter,
+    invalid_context_policy: InvalidContextPolicy,
+    propagator: Box<dyn Propagator>,
+    sampler: Box<dyn Sampler>,
     sender: SpanSender,
+    text_map_propagator: Box<dyn Propagator>,
+    trace_id_mode: TraceIdMode,
 }
 
 impl ZipkinTracer {
     /// Creates a new zipkin tracer.
     pub fn new() -> (Tracer, SpanReceiver) {
+        ZipkinTracer::new_with_options(ZipkinTracerOptions::default())
+    }
+
+    /// Creates a new zipkin tracer with the given options.
+    pub fn new_with_options(options: ZipkinTracerOptions) -> (Tracer, SpanReceiver) {
         let (sender, receiver) = unbounded();
-        let tracer = Tracer::new(ZipkinTracer { sender });
+        let tracer = Tracer::new(ZipkinTracer {
+            binary_propagator: options.binary_propagator,
+            id_generator: options.id_generator,
+            invalid_context_counter: options.invalid_context_counter,
+            invalid_context_policy: options.invalid_context_policy,
+            propagator: options.propagator,
+            sampler: options.sampler,
+            sender,
+            text_map_propagator: options.text_map_propagator,
+            trace_id_mode: options.trace_id_mode,
+        });
         (tracer, receiver)
     }
+
+    /// Applies the invalid context policy to an extracted context.
+    fn validate(&self, context: Option<SpanContext>) -> Result<Option<SpanContext>> {
+        let context = match context {
+            None => return Ok(None),
+            Some(context) => context,
+        };
+        let context = match self.trace_id_mode {
+            TraceIdMode::Padded => ZipkinContext::pad_trace_id(&context),
+            TraceIdMode::Long | TraceIdMode::Short => context,
+        };
+        match self::validation::validate(&context) {
+            Ok(()) => Ok(Some(context)),
+            Err(error) => {
+                self.invalid_context_counter.increment();
+                match self.invalid_context_policy {
+                    InvalidContextPolicy::Error => Err(error.into()),
+                    InvalidContextPolicy::NewTrace => Ok(None),
+                }
+            }
+        }
+    }
 }
 
 impl TracerInterface for ZipkinTracer {
     fn extract(&self, fmt: ExtractFormat) -> Result<Option<SpanContext>> {
-        match fmt {
-            ExtractFormat::Binary(carrier) => extract::binary(carrier),
-            ExtractFormat::HttpHeaders(carrier) => extract::http_headers(carrier),
-            ExtractFormat::TextMap(carrier) => extract::http_headers(carrier),
-        }
+        let context = match fmt {
+            ExtractFormat::Binary(carrier) => self.binary_propagator.extract(*carrier)?,
+            ExtractFormat::HttpHeaders(carrier) => self.propagator.extract(*carrier)?,
+            ExtractFormat::TextMap(carrier) => self.text_map_propagator.extract(*carrier)?,
+        };
+        self.validate(context)
     }
 
     fn inject(&self, context: &SpanContext, fmt: InjectFormat) -> Result<()> {
         match fmt {
-            InjectFormat::Binary(carrier) => inject::binary(context, carrier),
-            InjectFormat::HttpHeaders(carrier) => inject::http_headers(context, carrier),
-            InjectFormat::TextMap(carrier) => inject::http_headers(context, carrier),
+            InjectFormat::Binary(carrier) => self.binary_propagator.inject(context, *carrier),
+            InjectFormat::HttpHeaders(carrier) => self.propagator.inject(context, *carrier),
+            InjectFormat::TextMap(carrier) => self.text_map_propagator.inject(context, *carrier),
         }
     }
 
     fn span(&self, name: &str, options: StartOptions) -> Span {
-        let context = ZipkinContext::new();
+        let trace_id = self.id_generator.trace_id();
+        let trace_id = match self.trace_id_mode {
+            TraceIdMode::Long | TraceIdMode::Padded => trace_id,
+            TraceIdMode::Short => TraceID::from(trace_id.split().1.to_be_bytes()),
+        };
+        let context = ZipkinContextOptions::default()
+            .sampling_state(SamplingState::Defer)
+            .span_id(self.id_generator.span_id())
+            .trace_id(trace_id);
+        let context = ZipkinContext::new_with_options(context);
         let context = SpanContext::new(ImplContextBox::new(context));
-        Span::new(name, context, options, self.sender.clone())
+        let mut span = Span::new(name, context, options, self.sender.clone());
+
+        // Root spans and spans referencing a deferred context are sampled by the sampler.
+        let decision = {
+            let context = span.context().impl_context::<ZipkinContext>().unwrap();
+            if context.sampling_state() != SamplingState::Defer {
+                return span;
+            }
+            let decision = match context.debug() {
+                true => SamplingDecision::new(true).sampler("debug", true),
+                false => {
+                    let request = SamplingRequest::new(name, context.trace_id());
+                    self.sampler.sample(&request)
+                }
+            };
+            context.record_decision(&decision);
+            decision
+        };
+        for (tag, value) in decision.into_tags() {
+            span.tag(&tag, value);
+        }
+        span
+    }
+}
+
+
+/// Additional options to configure a `ZipkinTracer` with.
+pub struct ZipkinTracerOptions {
+    binary_propagator: Box<dyn BinaryPropagator>,
+    id_generator: Box<dyn IdGenerator>,
+    invalid_context_counter: InvalidContextCounter,
+    invalid_context_policy: InvalidContextPolicy,
+    propagator: Box<dyn Propagator>,
+    sampler: Box<dyn Sampler>,
+    text_map_propagator: Box<dyn Propagator>,
+    trace_id_mode: TraceIdMode,
+}
+
+impl ZipkinTracerOptions {
+    /// Sets the propagator to inject and extract binary carriers with.
+    pub fn binary_propagator<P>(mut self, propagator: P) -> ZipkinTracerOptions
+        where P: BinaryPropagator + 'static
+    {
+        self.binary_propagator = Box::new(propagator);
+        self
+    }
+
+    /// Sets the generator of span and trace IDs for new spans.
+    ///
+    /// IDs are random by default.
+    pub fn id_generator<G: IdGenerator + 'static>(mut self, generator: G) -> ZipkinTracerOptions {
+        self.id_generator = Box::new(generator);
+        self
+    }
+
+    /// Sets the counter to record invalid extracted contexts with.
+    ///
+    /// Keep a clone of the counter to read how many invalid contexts were seen.
+    pub fn invalid_context_counter(
+        mut self, counter: InvalidContextCounter
+    ) -> ZipkinTracerOptions {
+        self.invalid_context_counter = counter;
+        self
+    }
+
+    /// Sets what to do with extracted contexts that fail validation.
+    ///
+    /// By default extraction fails with an `InvalidContext` error.
+    pub fn invalid_context_policy(mut self, policy: InvalidContextPolicy) -> ZipkinTracerOptions {
+        self.invalid_context_policy = policy;
+        self
+    }
+
+    /// Sets the propagator to inject and extract HTTP headers with.
+    ///
+    /// Use a `CompositePropagator` to support more than one format.
+    pub fn propagator<P: Propagator + 'static>(mut self, propagator: P) -> ZipkinTracerOptions {
+        self.propagator = Box::new(propagator);
+        self
+    }
+
+    /// Sets the propagator to inject and extract text maps with.
+    ///
+    /// Text maps use a `B3MultiPropagator` by default, regardless of the propagator
+    /// used for HTTP headers: use a `TextMapPropagator` for non-HTTP carriers.
+    pub fn text_map_propagator<P>(mut self, propagator: P) -> ZipkinTracerOptions
+        where P: Propagator + 'static
+    {
+        self.text_map_propagator = Box::new(propagator);
+        self
+    }
+
+    /// Sets how trace ids are generated and propagated.
+    ///
+    /// By default 16 bytes ids are generated and extracted ids are propagated unchanged.
+    pub fn trace_id_mode(mut self, mode: TraceIdMode) -> ZipkinTracerOptions {
+        self.trace_id_mode = mode;
+        self
+    }
+
+    /// Sets the sampler to decide if root spans are sampled.
+    pub fn sampler<S: Sampler + 'static>(mut self, sampler: S) -> ZipkinTracerOptions {
+        self.sampler = Box::new(sampler);
+        self
+    }
+}
+
+impl Default for ZipkinTracerOptions {
+    fn default() -> ZipkinTracerOptions {
+        ZipkinTracerOptions {
+            binary_propagator: Box::new(ThriftBinaryPropagator::default()),
+            id_generator: Box::new(RandomIdGenerator),
+            invalid_context_counter: InvalidContextCounter::new(),
+            invalid_context_policy: InvalidContextPolicy::Error,
+            propagator: Box::new(CompositePropagator::default()),
+            sampler: Box::new(ConstSampler::always()),
+            text_map_propagator: Box::new(B3MultiPropagator),
+            trace_id_mode: TraceIdMode::Long,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+
+    use opentracingrust::ExtractFormat;
+    use opentracingrust::InjectFormat;
+    use opentracingrust::StartOptions;
+
+    use opentracingrust::ImplContextBox;
+    use opentracingrust::SpanContext;
+
+    use super::super::sampler::ConstSampler;
+    use super::B3SinglePropagator;
+    use super::CompositePropagator;
+    use super::GrpcTraceBinPropagator;
+    use super::InvalidContext;
+    use super::InvalidContextCounter;
+    use super::InvalidContextPolicy;
+    use super::JaegerPropagator;
+    use super::SamplingState;
+    use super::SeededIdGenerator;
+    use super::TextMapPropagator;
+    use super::TraceContextPropagator;
+    use super::TraceID;
+    use super::TraceIdMode;
+    use super::XRayPropagator;
+    use super::ZipkinContext;
+    use super::ZipkinContextOptions;
+    use super::ZipkinTracer;
+    use super::ZipkinTracerOptions;
+
+    fn sampled(span: &::opentracingrust::Span) -> bool {
+        span.context().impl_context::<ZipkinContext>().unwrap().sampled()
+    }
+
+    #[test]
+    fn root_spans_are_sampled_by_default() {
+        let (tracer, _) = ZipkinTracer::new();
+        let span = tracer.span("test");
+        assert!(sampled(&span));
+    }
+
+    #[test]
+    fn root_spans_use_the_sampler() {
+        let options = ZipkinTracerOptions::default().sampler(ConstSampler::never());
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let span = tracer.span("test");
+        assert!(!sampled(&span));
+    }
+
+    #[test]
+    fn child_spans_inherit_sampling() {
+        let (tracer, _) = ZipkinTracer::new();
+        let parent = tracer.span("parent");
+        let options = ZipkinTracerOptions::default().sampler(ConstSampler::never());
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let options = StartOptions::default().child_of(parent.context().clone());
+        let span = tracer.span_with_options("test", options);
+        assert!(sampled(&span));
+    }
+
+    #[test]
+    fn deferred_parents_use_the_sampler() {
+        let parent = ZipkinContextOptions::default().sampling_state(SamplingState::Defer);
+        let parent = ZipkinContext::new_with_options(parent);
+        let parent = SpanContext::new(ImplContextBox::new(parent));
+        let options = ZipkinTracerOptions::default().sampler(ConstSampler::never());
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let options = StartOptions::default().child_of(parent);
+        let span = tracer.span_with_options("test", options);
+        let context = span.context().impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(context.sampling_state(), SamplingState::Deny);
+    }
+
+    #[test]
+    fn inject_b3_single_header() {
+        let options = ZipkinTracerOptions::default()
+            .propagator(B3SinglePropagator);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let span = tracer.span("test");
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::HttpHeaders(Box::new(&mut headers));
+        tracer.inject(span.context(), format).unwrap();
+        assert!(headers.contains_key("b3"));
+        assert!(!headers.contains_key("X-B3-TraceId"));
+    }
+
+    #[test]
+    fn inject_all_propagators() {
+        let options = ZipkinTracerOptions::default()
+            .propagator(
+                CompositePropagator::new()
+                    .propagator(B3SinglePropagator)
+                    .propagator(TraceContextPropagator)
+            );
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let span = tracer.span("test");
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::HttpHeaders(Box::new(&mut headers));
+        tracer.inject(span.context(), format).unwrap();
+        assert!(headers.contains_key("traceparent"));
+        assert!(headers.contains_key("b3"));
+        assert!(!headers.contains_key("X-B3-TraceId"));
+    }
+
+    #[test]
+    fn text_maps_are_independent_of_http_headers() {
+        let options = ZipkinTracerOptions::default()
+            .propagator(B3SinglePropagator);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let span = tracer.span("test");
+        let mut items: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::TextMap(Box::new(&mut items));
+        tracer.inject(span.context(), format).unwrap();
+        assert!(items.contains_key("X-B3-TraceId"));
+        assert!(!items.contains_key("b3"));
+    }
+
+    #[test]
+    fn carrier_safe_text_maps() {
+        let options = ZipkinTracerOptions::default()
+            .text_map_propagator(TextMapPropagator);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let span = tracer.span("test");
+        let mut items: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::TextMap(Box::new(&mut items));
+        tracer.inject(span.context(), format).unwrap();
+        assert!(items.contains_key("x-b3-traceid"));
+    }
+
+    fn invalid_headers() -> HashMap<String, String> {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(String::from("X-B3-TraceId"), String::from("0000000000000000"));
+        headers.insert(String::from("X-B3-SpanId"), String::from("0000000000000002"));
+        headers
+    }
+
+    #[test]
+    fn extract_invalid_context_fails() {
+        let counter = InvalidContextCounter::new();
+        let options = ZipkinTracerOptions::default().invalid_context_counter(counter.clone());
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let headers = invalid_headers();
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let error = tracer.extract(format).unwrap_err();
+        assert_eq!(InvalidContext::from_error(&error), Some(InvalidContext::ZeroTraceId));
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[test]
+    fn extract_invalid_context_starts_new_trace() {
+        let counter = InvalidContextCounter::new();
+        let options = ZipkinTracerOptions::default()
+            .invalid_context_counter(counter.clone())
+            .invalid_context_policy(InvalidContextPolicy::NewTrace);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let headers = invalid_headers();
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        assert!(tracer.extract(format).unwrap().is_none());
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[test]
+    fn extract_zero_ids_in_all_formats() {
+        let extract = |options: ZipkinTracerOptions, format: &str| {
+            let counter = InvalidContextCounter::new();
+            let options = options.invalid_context_counter(counter.clone());
+            let (tracer, _) = ZipkinTracer::new_with_options(options);
+            let mut headers: HashMap<String, String> = HashMap::new();
+            let result = match format {
+                "grpc-trace-bin" => {
+                    let mut buffer = vec![0; 29];
+                    buffer[18] = 1;
+                    buffer[26] = 42;
+                    buffer[27] = 2;
+                    let mut cursor = Cursor::new(buffer);
+                    tracer.extract(ExtractFormat::Binary(Box::new(&mut cursor)))
+                }
+                header => {
+                    let value = match header {
+                        "traceparent" => "00-00000000000000000000000000000000-000000000000002a-01",
+                        _ => "0:2a:0:1",
+                    };
+                    headers.insert(String::from(header), String::from(value));
+                    tracer.extract(ExtractFormat::HttpHeaders(Box::new(&headers)))
+                }
+            };
+            (result, counter.count())
+        };
+        for &policy in [InvalidContextPolicy::Error, InvalidContextPolicy::NewTrace].iter() {
+            let formats = [
+                (ZipkinTracerOptions::default(), "traceparent"),
+                (ZipkinTracerOptions::default().propagator(JaegerPropagator), "uber-trace-id"),
+                (
+                    ZipkinTracerOptions::default().binary_propagator(GrpcTraceBinPropagator),
+                    "grpc-trace-bin",
+                ),
+            ];
+            for (options, format) in formats {
+                let (result, count) = extract(options.invalid_context_policy(policy), format);
+                match policy {
+                    InvalidContextPolicy::Error => {
+                        let error = result.unwrap_err();
+                        let invalid = InvalidContext::from_error(&error);
+                        assert_eq!(invalid, Some(InvalidContext::ZeroTraceId), "{}", format);
+                    }
+                    InvalidContextPolicy::NewTrace => {
+                        assert!(result.unwrap().is_none(), "{}", format);
+                    }
+                }
+                assert_eq!(count, 1, "{}", format);
+            }
+        }
+    }
+
+    #[test]
+    fn sampling_only_b3_starts_a_new_trace() {
+        let span = |seed| {
+            let options = ZipkinTracerOptions::default().id_generator(SeededIdGenerator::new(seed));
+            let (tracer, _) = ZipkinTracer::new_with_options(options);
+            let mut headers: HashMap<String, String> = HashMap::new();
+            headers.insert(String::from("b3"), String::from("d"));
+            let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+            let parent = tracer.extract(format).unwrap().unwrap();
+            let span = tracer.span_with_options("server", StartOptions::default().child_of(parent));
+            let context = span.context().impl_context::<ZipkinContext>().unwrap();
+            assert!(context.debug());
+            assert_eq!(context.parent_span_id(), None);
+            (*context.trace_id(), context.span_id())
+        };
+        assert_eq!(span(42), span(42));
+    }
+
+    #[test]
+    fn sampling_only_contexts_round_trip() {
+        let (tracer, _) = ZipkinTracer::new();
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(String::from("b3"), String::from("0"));
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let context = tracer.extract(format).unwrap().unwrap();
+
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::HttpHeaders(Box::new(&mut headers));
+        tracer.inject(&context, format).unwrap();
+        assert!(!headers.contains_key("X-B3-TraceId"));
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let context = tracer.extract(format).unwrap().unwrap();
+        let context = context.impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(context.sampling_state(), SamplingState::Deny);
+        assert_eq!(context.known_span_id(), None);
+        assert_eq!(context.known_trace_id(), None);
+    }
+
+    #[test]
+    fn load_balancer_xray_header_starts_a_root_span() {
+        let options = ZipkinTracerOptions::default().propagator(XRayPropagator);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let root = "Root=1-5759e988-bd862e3fe1be46a994272793";
+        headers.insert(String::from("X-Amzn-Trace-Id"), String::from(root));
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let parent = tracer.extract(format).unwrap().unwrap();
+        let span = tracer.span_with_options("server", StartOptions::default().child_of(parent));
+        let context = span.context().impl_context::<ZipkinContext>().unwrap();
+        let trace_id = TraceID::join(0x5759_e988_bd86_2e3f, 0xe1be_46a9_9427_2793);
+        assert_eq!(context.trace_id(), &trace_id);
+        assert_eq!(context.parent_span_id(), None);
+        assert_ne!(context.span_id(), 0);
+    }
+
+    #[test]
+    fn server_spans_join_extracted_contexts() {
+        let (tracer, _) = ZipkinTracer::new();
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(String::from("X-B3-TraceId"), String::from("0000000000000001"));
+        headers.insert(String::from("X-B3-SpanId"), String::from("0000000000000003"));
+        headers.insert(String::from("X-B3-ParentSpanId"), String::from("0000000000000002"));
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let parent = tracer.extract(format).unwrap().unwrap();
+        let options = StartOptions::default().child_of(ZipkinContext::join(&parent));
+        let span = tracer.span_with_options("server", options);
+        let context = span.context().impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(context.parent_span_id(), Some(2));
+        assert_eq!(context.span_id(), 3);
+        assert!(context.shared());
+
+        let options = StartOptions::default().child_of(parent);
+        let span = tracer.span_with_options("consumer", options);
+        let context = span.context().impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(context.parent_span_id(), Some(3));
+        assert!(!context.shared());
+    }
+
+    #[test]
+    fn generate_short_trace_ids() {
+        let options = ZipkinTracerOptions::default().trace_id_mode(TraceIdMode::Short);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let span = tracer.span("test");
+        let context = span.context().impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(context.trace_id().to_string().len(), 16);
+    }
+
+    #[test]
+    fn short_trace_ids_for_sampling_only_contexts() {
+        let options = ZipkinTracerOptions::default().trace_id_mode(TraceIdMode::Short);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(String::from("b3"), String::from("d"));
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let parent = tracer.extract(format).unwrap().unwrap();
+        let span = tracer.span_with_options("test", StartOptions::default().child_of(parent));
+
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::HttpHeaders(Box::new(&mut headers));
+        tracer.inject(span.context(), format).unwrap();
+        assert_eq!(headers["X-B3-TraceId"].len(), 16);
+        assert_eq!(headers["X-B3-Flags"], "1");
+    }
+
+    #[test]
+    fn pad_extracted_short_trace_ids() {
+        let options = ZipkinTracerOptions::default().trace_id_mode(TraceIdMode::Padded);
+        let (tracer, _) = ZipkinTracer::new_with_options(options);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(String::from("X-B3-TraceId"), String::from("090a0b0c0d0e0f10"));
+        headers.insert(String::from("X-B3-SpanId"), String::from("0000000000000003"));
+        let format = ExtractFormat::HttpHeaders(Box::new(&headers));
+        let parent = tracer.extract(format).unwrap().unwrap();
+        let span = tracer.span_with_options("test", StartOptions::default().child_of(parent));
+
+        let mut headers: HashMap<String, String> = HashMap::new();
+        let format = InjectFormat::HttpHeaders(Box::new(&mut headers));
+        tracer.inject(span.context(), format).unwrap();
+        assert_eq!(headers["X-B3-TraceId"], "0000000000000000090a0b0c0d0e0f10");
+        let context = span.context().impl_context::<ZipkinContext>().unwrap();
+        assert_eq!(context.trace_id(), &TraceID::join(0, 0x090a_0b0c_0d0e_0f10));
+    }
+
+    #[test]
+    fn ids_use_the_generator() {
+        let ids = |seed| {
+            let options = ZipkinTracerOptions::default().id_generator(SeededIdGenerator::new(seed));
+            let (tracer, _) = ZipkinTracer::new_with_options(options);
+            let parent = tracer.span("parent");
+            let options = StartOptions::default().child_of(parent.context().clone());
+            let span = tracer.span_with_options("test", options);
+            let context = span.context().impl_context::<ZipkinContext>().unwrap();
+            (*context.trace_id(), context.parent_span_id(), context.span_id())
+        };
+        assert_eq!(ids(42), ids(42));
+        assert_ne!(ids(42), ids(43));
     }
 }
@@ -0,0 +1,624 @@
+use std::io::Read;
+use std::io::Write;
+use std::sync::Arc;
+
+use opentracingrust::MapCarrier;
+use opentracingrust::Result;
+use opentracingrust::SpanContext;
+
+use super::extract;
+use super::inject;
+
+
+/// Injects and extracts `SpanContext`s to and from HTTP headers or text maps.
+///
+/// Propagators must be able to handle `SpanContext`s created by the `ZipkinTracer`.
+/// Custom formats can be supported by implementing this trait and passing
+/// the propagator to `ZipkinTracerOptions::propagator` or
+/// `ZipkinTracerOptions::text_map_propagator` (on its own or as
+/// part of a `CompositePropagator`).
+pub trait Propagator: Send + Sync {
+    /// Encode the context into the carrier.
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()>;
+
+    /// Decode a context from the carrier.
+    ///
+    /// Returns `Ok(None)` if the carrier has no context in the propagator's format.
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>>;
+}
+
+impl<P: Propagator + ?Sized> Propagator for Box<P> {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        (**self).inject(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        (**self).extract(carrier)
+    }
+}
+
+impl<P: Propagator + ?Sized> Propagator for Arc<P> {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        (**self).inject(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        (**self).extract(carrier)
+    }
+}
+
+
+/// Injects and extracts `SpanContext`s to and from binary carriers.
+///
+/// Binary propagators are used for `InjectFormat::Binary` and `ExtractFormat::Binary`
+/// and are configured with `ZipkinTracerOptions::binary_propagator`.
+pub trait BinaryPropagator: Send + Sync {
+    /// Encode the context into the carrier.
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn Write) -> Result<()>;
+
+    /// Decode a context from the carrier.
+    fn extract(&self, carrier: &mut dyn Read) -> Result<Option<SpanContext>>;
+}
+
+impl<P: BinaryPropagator + ?Sized> BinaryPropagator for Box<P> {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn Write) -> Result<()> {
+        (**self).inject(context, carrier)
+    }
+
+    fn extract(&self, carrier: &mut dyn Read) -> Result<Option<SpanContext>> {
+        (**self).extract(carrier)
+    }
+}
+
+impl<P: BinaryPropagator + ?Sized> BinaryPropagator for Arc<P> {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn Write) -> Result<()> {
+        (**self).inject(context, carrier)
+    }
+
+    fn extract(&self, carrier: &mut dyn Read) -> Result<Option<SpanContext>> {
+        (**self).extract(carrier)
+    }
+}
+
+
+/// Magic bytes at the start of versioned binary contexts.
+pub(crate) const BINARY_MAGIC: [u8; 2] = *b"ZK";
+
+/// Version of the binary context layout.
+pub(crate) const BINARY_VERSION: u8 = 1;
+
+
+/// Thrift protocols to encode binary contexts with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ThriftProtocol {
+    /// The thrift binary protocol.
+    Binary,
+
+    /// The thrift compact protocol, which produces smaller contexts.
+    Compact,
+}
+
+impl ThriftProtocol {
+    /// Identifier of the protocol in the binary context header.
+    pub(crate) fn id(self) -> u8 {
+        match self {
+            ThriftProtocol::Binary => 0,
+            ThriftProtocol::Compact => 1,
+        }
+    }
+
+    /// Find the protocol for an identifier in the binary context header.
+    pub(crate) fn from_id(id: u8) -> Option<ThriftProtocol> {
+        match id {
+            0 => Some(ThriftProtocol::Binary),
+            1 => Some(ThriftProtocol::Compact),
+            _ => None,
+        }
+    }
+}
+
+
+/// Propagates contexts as thrift encoded structures.
+///
+/// This is the default binary format and is only understood by other
+/// users of this crate.
+///
+/// Contexts can be preceded by a small header (magic bytes, version and thrift protocol)
+/// so extraction can tell Zipkin contexts from other data and reject unknown versions.
+/// Contexts with and without the header are extracted but, by default, contexts are
+/// injected without it (and with the thrift binary protocol) because older versions of
+/// this crate can't extract them: enable `versioned` once all services are upgraded.
+#[derive(Clone, Copy, Debug)]
+pub struct ThriftBinaryPropagator {
+    accept_unversioned: bool,
+    protocol: ThriftProtocol,
+    versioned: bool,
+}
+
+impl ThriftBinaryPropagator {
+    /// Returns a propagator with the default options.
+    pub fn new() -> ThriftBinaryPropagator {
+        ThriftBinaryPropagator {
+            accept_unversioned: true,
+            protocol: ThriftProtocol::Binary,
+            versioned: false,
+        }
+    }
+
+    /// Set whether contexts without a version header are extracted.
+    pub fn accept_unversioned(mut self, accept: bool) -> ThriftBinaryPropagator {
+        self.accept_unversioned = accept;
+        self
+    }
+
+    /// Set the thrift protocol to inject versioned contexts with.
+    ///
+    /// Extraction supports all protocols regardless of this option.
+    pub fn protocol(mut self, protocol: ThriftProtocol) -> ThriftBinaryPropagator {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set whether injected contexts have a version header (disabled by default).
+    ///
+    /// Only enable the header once no service running older versions of this crate
+    /// needs to extract the injected contexts.
+    /// Unversioned contexts are always encoded with the thrift binary protocol.
+    pub fn versioned(mut self, versioned: bool) -> ThriftBinaryPropagator {
+        self.versioned = versioned;
+        self
+    }
+}
+
+impl Default for ThriftBinaryPropagator {
+    fn default() -> ThriftBinaryPropagator {
+        ThriftBinaryPropagator::new()
+    }
+}
+
+impl BinaryPropagator for ThriftBinaryPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn Write) -> Result<()> {
+        match self.versioned {
+            false => inject::binary(context, carrier),
+            true => inject::versioned_binary(context, carrier, self.protocol),
+        }
+    }
+
+    fn extract(&self, carrier: &mut dyn Read) -> Result<Option<SpanContext>> {
+        extract::versioned_binary(carrier, self.accept_unversioned)
+    }
+}
+
+
+/// Propagates contexts with the OpenCensus `grpc-trace-bin` binary format.
+///
+/// This is the format gRPC peers (such as Go and Java services instrumented with
+/// OpenCensus) use in the `grpc-trace-bin` binary metadata.
+/// The format has no deferred sampling state, parent span ID or baggage items.
+/// See https://github.com/census-instrumentation/opencensus-specs/blob/master/encodings/BinaryEncoding.md
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GrpcTraceBinPropagator;
+
+impl BinaryPropagator for GrpcTraceBinPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn Write) -> Result<()> {
+        inject::grpc_trace_bin(context, carrier)
+    }
+
+    fn extract(&self, carrier: &mut dyn Read) -> Result<Option<SpanContext>> {
+        extract::grpc_trace_bin(carrier)
+    }
+}
+
+
+/// Propagates contexts with the multiple `X-B3-*` headers.
+///
+/// See https://github.com/openzipkin/b3-propagation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct B3MultiPropagator;
+
+impl Propagator for B3MultiPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        inject::b3_multi_headers(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        extract::b3_multi_headers(carrier)
+    }
+}
+
+
+/// Propagates contexts with the single `b3` header.
+///
+/// See https://github.com/openzipkin/b3-propagation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct B3SinglePropagator;
+
+impl Propagator for B3SinglePropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        inject::b3_single_header(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        extract::b3_single_header(carrier)
+    }
+}
+
+
+/// Propagates contexts with the W3C Trace Context `traceparent` and `tracestate` headers.
+///
+/// See https://www.w3.org/TR/trace-context/
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceContextPropagator;
+
+impl Propagator for TraceContextPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        inject::trace_context(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        extract::trace_context(carrier)
+    }
+}
+
+
+/// Propagates contexts with the Jaeger `uber-trace-id` and `uberctx-*` headers.
+///
+/// Jaeger has no deferred sampling decision: contexts that deferred
+/// the decision are injected as not sampled.
+/// See https://www.jaegertracing.io/docs/latest/client-libraries/#propagation-format
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JaegerPropagator;
+
+impl Propagator for JaegerPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        inject::jaeger(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        extract::jaeger(carrier)
+    }
+}
+
+
+/// Propagates contexts with the AWS X-Ray `X-Amzn-Trace-Id` header.
+///
+/// X-Ray root trace IDs (`1-{epoch}-{unique-id}`) map to 128-bit Zipkin trace IDs
+/// so that ids assigned by AWS load balancers can be correlated with X-Ray logs.
+///
+/// X-Ray rejects trace IDs with an epoch far from the current time, so tracers that inject
+/// new traces in this format need a `TimeIdGenerator` and `TraceIdMode::Long`:
+/// random trace IDs have a random epoch and short trace IDs are padded to a zero epoch.
+/// See https://docs.aws.amazon.com/xray/latest/devguide/xray-concepts.html#xray-concepts-tracingheader
+#[derive(Clone, Copy, Debug, Default)]
+pub struct XRayPropagator;
+
+impl Propagator for XRayPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        inject::xray(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        extract::xray(carrier)
+    }
+}
+
+
+/// Propagates contexts with text maps that are safe for non-HTTP carriers.
+///
+/// Meant for `InjectFormat::TextMap` and `ExtractFormat::TextMap` carriers such as
+/// Kafka, AMQP message headers or SQS attributes.
+/// All keys are lowercase and baggage keys and values are percent-encoded
+/// so that arbitrary baggage items round-trip.
+/// See `ZipkinTracerOptions::text_map_propagator`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TextMapPropagator;
+
+impl Propagator for TextMapPropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        inject::text_map(context, carrier)
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        extract::text_map(carrier)
+    }
+}
+
+
+/// Propagates contexts with an ordered list of propagators.
+///
+/// Injection writes the context with all the propagators, in order.
+/// Extraction returns the context from the first propagator that finds one:
+/// if that propagator fails to decode its headers the error is returned
+/// and later propagators are not tried.
+///
+/// Propagators can also be added for injection or extraction only.
+/// This is useful to migrate between formats: services can accept both
+/// formats long before they start sending the new one.
+///
+/// The default propagator injects the multiple `X-B3-*` headers and extracts
+/// the single `b3` header, the multiple `X-B3-*` headers or W3C Trace Context
+/// headers (in this order).
+///
+/// # Example
+///
+/// ```ignore
+/// let propagator = CompositePropagator::new()
+///     .propagator(B3MultiPropagator)
+///     .propagator(TraceContextPropagator);
+/// let options = ZipkinTracerOptions::default().propagator(propagator);
+/// let (tracer, receiver) = ZipkinTracer::new_with_options(options);
+/// ```
+pub struct CompositePropagator {
+    extractors: Vec<Arc<dyn Propagator>>,
+    injectors: Vec<Arc<dyn Propagator>>,
+}
+
+impl CompositePropagator {
+    /// Returns a propagator with an empty list of propagators.
+    pub fn new() -> CompositePropagator {
+        CompositePropagator {
+            extractors: Vec::new(),
+            injectors: Vec::new(),
+        }
+    }
+
+    /// Appends a propagator used for both injection and extraction.
+    ///
+    /// The same propagator instance is used for injection and extraction.
+    pub fn propagator<P: Propagator + 'static>(mut self, propagator: P) -> CompositePropagator {
+        let propagator: Arc<dyn Propagator> = Arc::new(propagator);
+        self.extractors.push(Arc::clone(&propagator));
+        self.injectors.push(propagator);
+        self
+    }
+
+    /// Appends a propagator used for extraction only.
+    pub fn extractor<P: Propagator + 'static>(mut self, propagator: P) -> CompositePropagator {
+        self.extractors.push(Arc::new(propagator));
+        self
+    }
+
+    /// Appends a propagator used for injection only.
+    pub fn injector<P: Propagator + 'static>(mut self, propagator: P) -> CompositePropagator {
+        self.injectors.push(Arc::new(propagator));
+        self
+    }
+}
+
+impl Default for CompositePropagator {
+    fn default() -> CompositePropagator {
+        CompositePropagator::new()
+            .injector(B3MultiPropagator)
+            .extractor(B3SinglePropagator)
+            .extractor(B3MultiPropagator)
+            .extractor(TraceContextPropagator)
+    }
+}
+
+impl Propagator for CompositePropagator {
+    fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+        for propagator in &self.injectors {
+            propagator.inject(context, carrier)?;
+        }
+        Ok(())
+    }
+
+    fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+        for propagator in &self.extractors {
+            if let Some(context) = propagator.extract(carrier)? {
+                return Ok(Some(context));
+            }
+        }
+        Ok(None)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use opentracingrust::ImplContextBox;
+    use opentracingrust::MapCarrier;
+    use opentracingrust::Result;
+    use opentracingrust::SpanContext;
+
+    use super::super::context::ZipkinContext;
+    use super::super::context::ZipkinContextOptions;
+    use super::B3MultiPropagator;
+    use super::BinaryPropagator;
+    use super::B3SinglePropagator;
+    use super::CompositePropagator;
+    use super::GrpcTraceBinPropagator;
+    use super::JaegerPropagator;
+    use super::Propagator;
+    use super::TextMapPropagator;
+    use super::ThriftBinaryPropagator;
+    use super::ThriftProtocol;
+    use super::TraceContextPropagator;
+
+    fn context(span_id: u64) -> SpanContext {
+        let options = ZipkinContextOptions::default().span_id(span_id);
+        SpanContext::new(ImplContextBox::new(ZipkinContext::new_with_options(options)))
+    }
+
+    fn span_id(context: &SpanContext) -> u64 {
+        context.impl_context::<ZipkinContext>().unwrap().span_id()
+    }
+
+    #[test]
+    fn inject_all_formats() {
+        let propagator = CompositePropagator::new()
+            .propagator(B3MultiPropagator)
+            .propagator(TraceContextPropagator);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        propagator.inject(&context(42), &mut headers).unwrap();
+        assert!(headers.contains_key("X-B3-TraceId"));
+        assert!(headers.contains_key("traceparent"));
+        assert!(!headers.contains_key("b3"));
+    }
+
+    #[test]
+    fn extract_first_match() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        B3SinglePropagator.inject(&context(1), &mut headers).unwrap();
+        TraceContextPropagator.inject(&context(2), &mut headers).unwrap();
+
+        let propagator = CompositePropagator::new()
+            .propagator(TraceContextPropagator)
+            .propagator(B3SinglePropagator);
+        let context = propagator.extract(&headers).unwrap().unwrap();
+        assert_eq!(span_id(&context), 2);
+
+        let propagator = CompositePropagator::new()
+            .propagator(B3MultiPropagator)
+            .propagator(B3SinglePropagator);
+        let context = propagator.extract(&headers).unwrap().unwrap();
+        assert_eq!(span_id(&context), 1);
+    }
+
+    #[test]
+    fn extract_nothing() {
+        let headers: HashMap<String, String> = HashMap::new();
+        let propagator = CompositePropagator::default();
+        assert!(propagator.extract(&headers).unwrap().is_none());
+    }
+
+    #[test]
+    fn extract_errors_stop_the_search() {
+        let mut headers: HashMap<String, String> = HashMap::new();
+        headers.insert(String::from("b3"), String::from("not-valid-x"));
+        TraceContextPropagator.inject(&context(2), &mut headers).unwrap();
+        let propagator = CompositePropagator::default();
+        assert!(propagator.extract(&headers).is_err());
+    }
+
+    #[test]
+    fn default_extracts_all_but_injects_b3_multi() {
+        let propagator = CompositePropagator::default();
+        let mut headers: HashMap<String, String> = HashMap::new();
+        propagator.inject(&context(42), &mut headers).unwrap();
+        assert!(headers.contains_key("X-B3-TraceId"));
+        assert!(!headers.contains_key("b3"));
+        assert!(!headers.contains_key("traceparent"));
+
+        let mut headers: HashMap<String, String> = HashMap::new();
+        TraceContextPropagator.inject(&context(42), &mut headers).unwrap();
+        let context = propagator.extract(&headers).unwrap().unwrap();
+        assert_eq!(span_id(&context), 42);
+    }
+
+    #[test]
+    fn extract_only() {
+        let propagator = CompositePropagator::new()
+            .injector(B3MultiPropagator)
+            .extractor(TraceContextPropagator);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        propagator.inject(&context(42), &mut headers).unwrap();
+        assert!(propagator.extract(&headers).unwrap().is_none());
+    }
+
+    /// A propagator that can't be cloned and counts how many times it is used.
+    struct CountingPropagator(Arc<AtomicUsize>);
+
+    impl Propagator for CountingPropagator {
+        fn inject(&self, context: &SpanContext, carrier: &mut dyn MapCarrier) -> Result<()> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            B3MultiPropagator.inject(context, carrier)
+        }
+
+        fn extract(&self, carrier: &dyn MapCarrier) -> Result<Option<SpanContext>> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            B3MultiPropagator.extract(carrier)
+        }
+    }
+
+    #[test]
+    fn propagators_are_shared() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let propagator = CompositePropagator::new()
+            .propagator(CountingPropagator(Arc::clone(&count)));
+        let mut headers: HashMap<String, String> = HashMap::new();
+        propagator.inject(&context(42), &mut headers).unwrap();
+        let context = propagator.extract(&headers).unwrap().unwrap();
+        assert_eq!(span_id(&context), 42);
+        assert_eq!(count.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn jaeger_round_trip() {
+        let propagator = CompositePropagator::new()
+            .propagator(B3MultiPropagator)
+            .propagator(JaegerPropagator);
+        let mut headers: HashMap<String, String> = HashMap::new();
+        JaegerPropagator.inject(&context(42), &mut headers).unwrap();
+        let context = propagator.extract(&headers).unwrap().unwrap();
+        assert_eq!(span_id(&context), 42);
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        let propagators: [&dyn BinaryPropagator; 4] = [
+            &ThriftBinaryPropagator::new(),
+            &ThriftBinaryPropagator::new().versioned(true),
+            &ThriftBinaryPropagator::new().versioned(true).protocol(ThriftProtocol::Compact),
+            &GrpcTraceBinPropagator,
+        ];
+        for propagator in propagators.iter() {
+            let mut buffer: Vec<u8> = Vec::new();
+            propagator.inject(&context(42), &mut buffer).unwrap();
+            let mut cursor = Cursor::new(buffer);
+            let context = propagator.extract(&mut cursor).unwrap().unwrap();
+            assert_eq!(span_id(&context), 42);
+        }
+    }
+
+    #[test]
+    fn text_map_round_trip() {
+        let mut context = context(42);
+        context.set_baggage_item(String::from("Key With Spaces"), String::from("a=b;c,d"));
+        let mut items: HashMap<String, String> = HashMap::new();
+        TextMapPropagator.inject(&context, &mut items).unwrap();
+        let items: HashMap<String, String> = items.into_iter()
+            .map(|(key, value)| (key.to_lowercase(), value))
+            .collect();
+        let context = TextMapPropagator.extract(&items).unwrap().unwrap();
+        assert_eq!(span_id(&context), 42);
+        assert_eq!(context.get_baggage_item("Key With Spaces").unwrap(), "a=b;c,d");
+    }
+
+    #[test]
+    fn thrift_versions() {
+        let mut unversioned: Vec<u8> = Vec::new();
+        ThriftBinaryPropagator::new().inject(&context(42), &mut unversioned).unwrap();
+        assert_ne!(&unversioned[..2], b"ZK");
+        let propagator = ThriftBinaryPropagator::new().accept_unversioned(false);
+        assert!(propagator.extract(&mut Cursor::new(unversioned)).is_err());
+
+        let mut future: Vec<u8> = Vec::new();
+        ThriftBinaryPropagator::new().versioned(true).inject(&context(42), &mut future).unwrap();
+        assert_eq!(&future[..4], b"ZK\x01\x00");
+        future[2] = 2;
+        match ThriftBinaryPropagator::new().extract(&mut Cursor::new(future)) {
+            Err(::opentracingrust::Error::Msg(message)) => {
+                assert_eq!(message, "Unsupported binary context version 2");
+            }
+            _ => panic!("Future versions should be rejected"),
+        };
+    }
+
+    #[test]
+    fn thrift_compact_is_smaller() {
+        let mut binary: Vec<u8> = Vec::new();
+        ThriftBinaryPropagator::new().versioned(true).inject(&context(42), &mut binary).unwrap();
+        let mut compact: Vec<u8> = Vec::new();
+        ThriftBinaryPropagator::new().versioned(true).protocol(ThriftProtocol::Compact)
+            .inject(&context(42), &mut compact).unwrap();
+        assert_eq!(compact[3], 1);
+        assert!(compact.len() < binary.len());
+    }
+}
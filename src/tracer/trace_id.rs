@@ -1,3 +1,4 @@
+use std::cmp::Ordering;
 use std::fmt;
 use std::io::Cursor;
 use std::str::FromStr;
@@ -40,19 +41,45 @@ fn u64_to_buffer(input: u64) -> Vec<u8> {
 }
 
 /// Inner container for long or short trace ids.
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 enum InnerID {
     Long([u8; 16]),
     Short([u8; 8]),
 }
 
+/// Trace ID reported by contexts that do not have one.
+pub(crate) const ZERO_TRACE_ID: TraceID = TraceID(InnerID::Short([0; 8]));
+
+
+/// How the tracer generates and propagates trace ids.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceIdMode {
+    /// Generate 16 bytes ids and propagate extracted ids unchanged.
+    Long,
+
+    /// Generate 16 bytes ids and left-pad extracted 8 bytes ids with zeros.
+    ///
+    /// Downstream services always receive 128-bit ids.
+    Padded,
+
+    /// Generate 8 bytes ids, for services that only accept 64-bit ids.
+    ///
+    /// Extracted 16 bytes ids are propagated unchanged.
+    Short,
+}
+
+
 /// Zipkin trace identifier.
 ///
 /// Zipkin trace ids can be short (8 bytes) or long (16 bytes).
 /// By default, new ids are long trace id.
 ///
-/// Identifiers can be converted to and decoded from strings.
-#[derive(Clone, Debug, PartialEq)]
+/// Identifiers can be converted to and decoded from strings, bytes and `u128`s.
+///
+/// Short ids are not equal to the long ids with the same value: use `TraceID::padded`
+/// to compare ids received in different formats.
+/// Ids are ordered by value, with short ids before long ids of the same value.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct TraceID(InnerID);
 
 impl TraceID {
@@ -68,12 +95,52 @@ impl TraceID {
     }
 
     /// Generate a new, random, 16 bytes ID.
+    ///
+    /// `TraceID` does not implement `Default`: a random default would give
+    /// unrelated IDs to structures that derive it.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> TraceID {
         TraceID(InnerID::Long(random::<[u8; 16]>()))
     }
 }
 
 impl TraceID {
+    /// Access the bytes of the id: 8 bytes for short ids and 16 bytes for long ids.
+    pub fn as_bytes(&self) -> &[u8] {
+        match self.0 {
+            InnerID::Long(ref id) => id,
+            InnerID::Short(ref id) => id,
+        }
+    }
+
+    /// Is this a short (8 bytes) id?
+    pub fn is_short(&self) -> bool {
+        match self.0 {
+            InnerID::Long(_) => false,
+            InnerID::Short(_) => true,
+        }
+    }
+
+    /// Returns the id as 16 bytes, left-padding short ids with zeros.
+    pub fn to_bytes(&self) -> [u8; 16] {
+        match self.0 {
+            InnerID::Long(id) => id,
+            InnerID::Short(id) => {
+                let mut padded = [0; 16];
+                padded[8..].copy_from_slice(&id);
+                padded
+            }
+        }
+    }
+
+    /// Returns the id as a 16 bytes ID, left-padding short ids with zeros.
+    pub fn padded(&self) -> TraceID {
+        match self.0 {
+            InnerID::Long(_) => *self,
+            InnerID::Short(_) => TraceID::from(self.to_bytes()),
+        }
+    }
+
     /// Returns the id as a (u64, u64) tuple.
     pub fn split(&self) -> (u64, u64) {
         match self.0 {
@@ -90,13 +157,22 @@ impl TraceID {
     }
 }
 
+impl Ord for TraceID {
+    fn cmp(&self, other: &TraceID) -> Ordering {
+        u128::from(*self).cmp(&u128::from(*other))
+            .then_with(|| other.is_short().cmp(&self.is_short()))
+    }
+}
+
+impl PartialOrd for TraceID {
+    fn partial_cmp(&self, other: &TraceID) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl fmt::Display for TraceID {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let id: &[u8] = match self.0 {
-            InnerID::Long(ref id) => id,
-            InnerID::Short(ref id) => id,
-        };
-        for byte in id {
+        for byte in self.as_bytes() {
             write!(fmt, "{:02x}", byte)?;
         }
         Ok(())
@@ -142,6 +218,19 @@ impl From<[u8; 16]> for TraceID {
     }
 }
 
+impl From<u128> for TraceID {
+    fn from(id: u128) -> TraceID {
+        TraceID::join((id >> 64) as u64, id as u64)
+    }
+}
+
+impl From<TraceID> for u128 {
+    fn from(id: TraceID) -> u128 {
+        let (high, low) = id.split();
+        (u128::from(high) << 64) | u128::from(low)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::InnerID;
@@ -156,6 +245,16 @@ mod tests {
         }
     }
 
+    #[test]
+    fn pad_short_id() {
+        let id = TraceID::from([9, 10, 11, 12, 13, 14, 15, 16]);
+        assert_eq!(
+            id.padded(),
+            TraceID::from([0, 0, 0, 0, 0, 0, 0, 0, 9, 10, 11, 12, 13, 14, 15, 16]),
+        );
+        assert_eq!(id.padded().padded(), id.padded());
+    }
+
     #[test]
     fn id_si_random() {
         let id1 = TraceID::new();
@@ -288,4 +387,45 @@ mod tests {
             );
         }
     }
+
+    mod traits {
+        use std::collections::HashSet;
+
+        use super::super::TraceID;
+
+        #[test]
+        fn bytes() {
+            let id = TraceID::from([9, 10, 11, 12, 13, 14, 15, 16]);
+            assert!(id.is_short());
+            assert_eq!(id.as_bytes(), &[9, 10, 11, 12, 13, 14, 15, 16]);
+            assert_eq!(id.to_bytes(), [0, 0, 0, 0, 0, 0, 0, 0, 9, 10, 11, 12, 13, 14, 15, 16]);
+            assert!(!id.padded().is_short());
+        }
+
+        #[test]
+        fn u128s() {
+            let id = TraceID::from(0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10_u128);
+            assert_eq!(id.to_string(), "0102030405060708090a0b0c0d0e0f10");
+            assert_eq!(u128::from(id), 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10);
+            assert_eq!(u128::from(TraceID::from([0, 0, 0, 0, 0, 0, 0, 55])), 55);
+        }
+
+        #[test]
+        fn ordering() {
+            let short = TraceID::from([0, 0, 0, 0, 0, 0, 0, 2]);
+            assert!(TraceID::join(0, 1) < short);
+            assert!(short < short.padded());
+            assert!(short.padded() < TraceID::join(1, 0));
+        }
+
+        #[test]
+        fn hash_keys() {
+            let short = TraceID::from([0, 0, 0, 0, 0, 0, 0, 2]);
+            let mut ids = HashSet::new();
+            ids.insert(short);
+            ids.insert(short);
+            ids.insert(short.padded());
+            assert_eq!(ids.len(), 2);
+        }
+    }
 }
@@ -0,0 +1,167 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use opentracingrust::Error;
+use opentracingrust::SpanContext;
+
+use super::context::ZipkinContext;
+use super::trace_id::TraceID;
+
+
+/// Reasons an extracted `SpanContext` is invalid.
+///
+/// Extraction surfaces these as `opentracingrust::Error::IoError` errors of kind
+/// `InvalidData`: use `InvalidContext::from_error` to check for them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidContext {
+    /// The parent span ID is the same as the span ID.
+    ParentIsSelf,
+
+    /// The span ID is zero.
+    ZeroSpanId,
+
+    /// The trace ID is all zeros.
+    ZeroTraceId,
+}
+
+impl InvalidContext {
+    /// Find the `InvalidContext` wrapped in an extraction error, if any.
+    pub fn from_error(error: &Error) -> Option<InvalidContext> {
+        match error {
+            Error::IoError(error) => error.get_ref()
+                .and_then(|inner| inner.downcast_ref::<InvalidContext>())
+                .cloned(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for InvalidContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            InvalidContext::ParentIsSelf => "parent span ID is the same as the span ID",
+            InvalidContext::ZeroSpanId => "span ID is zero",
+            InvalidContext::ZeroTraceId => "trace ID is zero",
+        };
+        write!(f, "Invalid span context: {}", reason)
+    }
+}
+
+impl error::Error for InvalidContext {}
+
+impl From<InvalidContext> for Error {
+    fn from(error: InvalidContext) -> Error {
+        Error::IoError(io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+
+/// What to do when an extracted `SpanContext` is invalid.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InvalidContextPolicy {
+    /// Fail the extraction with an `InvalidContext` error.
+    Error,
+
+    /// Ignore the context so that callers start a new trace.
+    NewTrace,
+}
+
+
+/// Counts extracted contexts that failed validation.
+///
+/// Clones share the same count so a counter can be given to
+/// `ZipkinTracerOptions::invalid_context_counter` and read later.
+#[derive(Clone, Debug, Default)]
+pub struct InvalidContextCounter(Arc<AtomicUsize>);
+
+impl InvalidContextCounter {
+    /// Returns a counter that has seen no invalid context.
+    pub fn new() -> InvalidContextCounter {
+        InvalidContextCounter::default()
+    }
+
+    /// Number of invalid contexts seen.
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Record an invalid context.
+    pub(crate) fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+
+/// Checks the identifiers of an extracted context according to the B3 specification.
+///
+/// Missing identifiers are valid: the context only carries sampling flags or the trace ID.
+pub fn validate(context: &SpanContext) -> Result<(), InvalidContext> {
+    let context = context.impl_context::<ZipkinContext>().expect(
+        "Invalid SpanContext, was it created by ZipkinTracer?"
+    );
+    if context.known_trace_id().map(TraceID::split) == Some((0, 0)) {
+        return Err(InvalidContext::ZeroTraceId);
+    }
+    if context.known_span_id() == Some(0) {
+        return Err(InvalidContext::ZeroSpanId);
+    }
+    if context.known_span_id().is_some() && context.parent_span_id() == context.known_span_id() {
+        return Err(InvalidContext::ParentIsSelf);
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use opentracingrust::Error;
+    use opentracingrust::ImplContextBox;
+    use opentracingrust::SpanContext;
+
+    use super::super::context::ZipkinContext;
+    use super::super::context::ZipkinContextOptions;
+    use super::super::trace_id::TraceID;
+    use super::InvalidContext;
+    use super::InvalidContextCounter;
+    use super::validate;
+
+    fn context(options: ZipkinContextOptions) -> SpanContext {
+        SpanContext::new(ImplContextBox::new(ZipkinContext::new_with_options(options)))
+    }
+
+    #[test]
+    fn valid_context() {
+        let options = ZipkinContextOptions::default().parent_span_id(1).span_id(2);
+        assert_eq!(validate(&context(options)), Ok(()));
+    }
+
+    #[test]
+    fn invalid_contexts() {
+        let options = ZipkinContextOptions::default().trace_id(TraceID::join(0, 0));
+        assert_eq!(validate(&context(options)), Err(InvalidContext::ZeroTraceId));
+        let options = ZipkinContextOptions::default().span_id(0);
+        assert_eq!(validate(&context(options)), Err(InvalidContext::ZeroSpanId));
+        let options = ZipkinContextOptions::default().parent_span_id(2).span_id(2);
+        assert_eq!(validate(&context(options)), Err(InvalidContext::ParentIsSelf));
+    }
+
+    #[test]
+    fn typed_errors() {
+        let error: Error = InvalidContext::ZeroSpanId.into();
+        assert_eq!(InvalidContext::from_error(&error), Some(InvalidContext::ZeroSpanId));
+        let error = Error::Msg(String::from("other"));
+        assert_eq!(InvalidContext::from_error(&error), None);
+    }
+
+    #[test]
+    fn counters_are_shared() {
+        let counter = InvalidContextCounter::new();
+        let clone = counter.clone();
+        clone.increment();
+        assert_eq!(counter.count(), 1);
+    }
+}